@@ -1,5 +1,5 @@
 extern crate grammar;
-use grammar::Grammar;
+use grammar::{Assoc, Grammar, Operators};
 use std::env;
 
 fn main()
@@ -9,15 +9,14 @@ fn main()
     g.rule("digit", "[0-9]");
     g.map("num", "(\\.<digit>+|<digit>+(\\.<digit>+)?)", |_, l| Ok(l.parse::<f64>().unwrap()));
     
-    g.rule("expr", " <add> ");
-    g.map("add", "<sub> (\\+ <sub>)*", |b, _| Ok(if b.len() == 1 { b[0] } else { b.iter().fold(0f64, |acc, &n| acc + n) }));
-    g.map("sub", "<mul> (- <mul>)*", |b, _| Ok(if b.len() == 1 { b[0] } else { b.iter().fold(b[0] * 2f64, |acc, &n| acc - n) }));
-    g.map("mul", "<div> (\\* <div>)*", |b, _| Ok(if b.len() == 1 { b[0] } else { b.iter().fold(1f64, |acc, &n| acc * n) }));
-    g.map("div", "<pow> (/ <pow>)*", |b, _| Ok(if b.len() == 1 { b[0] } else { b.iter().fold(b[0] * b[0], |acc, &n| acc / n) }));
-    g.map("pow", "<neg> (\\^ <neg>)?", |b, _| Ok(if b.len() == 1 { b[0] } else { b[0].powf(b[1]) }));
-    
-    g.map("neg-apply", "-<base>", |b, _| Ok(-b[0]));
-    g.rule("neg", "(<neg-apply>|<base>)");
+    g.rule("expr", " <arith> ");
+    g.operators("arith", "base", Operators::<f64>::new()
+        .infix(" \\+ ", 1, Assoc::Left, |a, b, _| Ok(a + b))
+        .infix(" - ", 1, Assoc::Left, |a, b, _| Ok(a - b))
+        .infix(" \\* ", 2, Assoc::Left, |a, b, _| Ok(a * b))
+        .infix(" / ", 2, Assoc::Left, |a, b, _| Ok(a / b))
+        .infix(" \\^ ", 3, Assoc::Right, |a, b, _| Ok(a.powf(b)))
+        .prefix("-", 4, |a, _| Ok(-a)));
 
     g.map("abs", "abs\\(<expr>\\)", |b, _| Ok(b[0].abs()));
     g.map("acos", "acos\\(<expr>\\)", |b, _| Ok(b[0].acos()));
@@ -0,0 +1,285 @@
+// Copyright (c) 2015-2023 Vincent van Ingen <code@abitvin.com>
+// Licensed under the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use super::ast::Clause;
+use super::{BranchFn, GrammarRule, GrammarRules};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+// A rule that is an alternation of which some alternatives start by calling the rule itself, like
+// `(<expr>\+<num>|<num>)`. It is scanned by matching one of the seeds and then growing the match with the tails for
+// as long as possible. Each time the branch function is called, so the results are left associative.
+#[derive(Clone)]
+pub struct LeftRecursive {
+    pub seeds: Vec<Vec<Clause>>,
+    pub tails: Vec<Tail>,
+}
+
+// An alternative without the call of the rule it starts with. `capture` is the capture name of that call, the values
+// of the match so far are captured by it.
+#[derive(Clone)]
+pub struct Tail {
+    pub capture: Option<String>,
+    pub sentence: Vec<Clause>,
+}
+
+impl LeftRecursive {
+    pub fn new(id: &str, sentence: &[Clause]) -> Option<Self> {
+        let alternatives = match sentence {
+            [Clause::AnyOf { lookahead: None, ref sentences, min: 1, max: 1 }] => sentences,
+            _ => return None,
+        };
+
+        let mut seeds = vec![];
+        let mut tails = vec![];
+
+        for x in alternatives {
+            match x.first() {
+                Some(Clause::Id { lookahead: None, ref name, ref capture, min: 1, max: 1 }) if name == id => {
+                    tails.push(Tail { capture: capture.clone(), sentence: x[1..].to_vec() });
+                },
+                _ => seeds.push(x.clone()),
+            }
+        }
+
+        if seeds.is_empty() || tails.is_empty() {
+            return None;
+        }
+
+        Some(LeftRecursive { seeds, tails })
+    }
+}
+
+// Which rules can match without consuming any input. The whitespace expression is kept apart because it has no id.
+pub struct Nullable<'a> {
+    rules: HashSet<&'a str>,
+    ws: bool,
+}
+
+impl<'a> Nullable<'a> {
+    pub fn new<T, C>(all_rules: &'a GrammarRules<T, C>, ws: &'a [Clause]) -> Self {
+        let mut nullable = Nullable { rules: HashSet::new(), ws: false };
+
+        // Keep going until nothing changes, a rule only becomes nullable through rules that already are.
+        loop {
+            let mut changed = false;
+
+            if !nullable.ws && nullable.sentence(ws) {
+                nullable.ws = true;
+                changed = true;
+            }
+
+            for (id, r) in all_rules {
+                if !nullable.rules.contains(id.as_str()) && nullable.rule(r) {
+                    nullable.rules.insert(id);
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                return nullable;
+            }
+        }
+    }
+
+    fn rule<T, C>(&self, rule: &GrammarRule<T, C>) -> bool {
+        match rule.left_recursive {
+            Some(ref x) => x.seeds.iter().any(|x| self.sentence(x)),
+            None => self.sentence(&rule.sentence),
+        }
+    }
+
+    pub fn sentence(&self, sentence: &[Clause]) -> bool {
+        sentence.iter().all(|x| self.clause(x))
+    }
+
+    pub fn clause(&self, clause: &Clause) -> bool {
+        match clause {
+            Clause::AlterTexts { ref replacements, min, .. } => *min == 0 || replacements.iter().any(|x| x.find.is_empty()),
+            Clause::AnyChar { lookahead, min, .. } => lookahead.is_some() || *min == 0,
+            Clause::AnyCharExcept { lookahead, min, .. } => lookahead.is_some() || *min == 0,
+            Clause::AnyOf { lookahead, ref sentences, min, .. } => lookahead.is_some() || *min == 0 || sentences.iter().any(|x| self.sentence(x)),
+            Clause::CharRanges { lookahead, min, .. } => lookahead.is_some() || *min == 0,
+            Clause::Eof => true,
+            Clause::Id { lookahead, ref name, min, .. } => lookahead.is_some() || *min == 0 || self.rules.contains(name.as_str()),
+            Clause::Literal { lookahead, ref text, min, .. } => lookahead.is_some() || *min == 0 || text.is_empty(),
+            Clause::NoBacktrack(_) => true,
+            Clause::Whitespace { min, .. } => *min == 0 || self.ws,
+        }
+    }
+}
+
+// Finds the rules that can call themselves without consuming any input, these would recurse forever. Rules that are
+// `LeftRecursive` are fine as long as their seeds are. Every cycle is returned as the path of rule ids from a rule
+// back to itself, e.g. `["expr", "add", "expr"]`.
+pub fn left_recursion<T, C>(all_rules: &GrammarRules<T, C>, ws: &[Clause]) -> Vec<Vec<String>> {
+    let nullable = Nullable::new(all_rules, ws);
+
+    let mut ws_calls = vec![];
+    left_calls(ws, &nullable, &[], &mut ws_calls);
+
+    let mut graph: HashMap<&str, Vec<&str>> = HashMap::new();
+
+    for (id, r) in all_rules {
+        let mut calls = vec![];
+
+        match r.left_recursive {
+            Some(ref x) => {
+                for seed in &x.seeds {
+                    left_calls(seed, &nullable, &ws_calls, &mut calls);
+                }
+
+                // The tails start where the seed ended, which is the start of the rule when the seed is empty.
+                if x.seeds.iter().any(|x| nullable.sentence(x)) {
+                    for tail in &x.tails {
+                        left_calls(&tail.sentence, &nullable, &ws_calls, &mut calls);
+                    }
+                }
+            },
+            None => {
+                left_calls(&r.sentence, &nullable, &ws_calls, &mut calls);
+            },
+        }
+
+        calls.dedup();
+        graph.insert(id, calls);
+    }
+
+    let mut ids: Vec<&str> = graph.keys().copied().collect();
+    ids.sort();
+
+    let mut cycles = BTreeSet::new();
+    let mut done = HashSet::new();
+
+    for id in ids {
+        let mut path = vec![];
+        find_cycles(id, &graph, &mut path, &mut done, &mut cycles);
+    }
+
+    cycles.into_iter().collect()
+}
+
+// The operators that can match without consuming any input, as the id of their rule and their expression. Scanning
+// would never get past such an operator, a postfix one matches forever and a prefix one recurses forever.
+pub fn empty_operators<T, C>(all_rules: &GrammarRules<T, C>, ws: &[Clause]) -> Vec<(String, String)> {
+    let nullable = Nullable::new(all_rules, ws);
+    let mut empty = vec![];
+
+    for (id, r) in all_rules {
+        for op in r.operators.iter().flat_map(|x| &x.ops) {
+            if nullable.sentence(&op.sentence) {
+                empty.push((id.clone(), op.expr.clone()));
+            }
+        }
+    }
+
+    empty.sort();
+    empty
+}
+
+// The rules with a context branch function and the rules that can call them, directly or through other rules. Their
+// results are not replayed from the memo table, that would skip the effect of the branch functions on the context.
+pub fn context_rules<T, C>(all_rules: &GrammarRules<T, C>, ws: &[Clause]) -> HashSet<String> {
+    let mut ws_calls = vec![];
+    all_calls(ws, &[], &mut ws_calls);
+
+    let mut graph: HashMap<&str, Vec<&str>> = HashMap::new();
+
+    for (id, r) in all_rules {
+        let mut calls = vec![];
+        all_calls(&r.sentence, &ws_calls, &mut calls);
+
+        if let Some(ref recovery) = r.recovery {
+            all_calls(&recovery.sentence, &ws_calls, &mut calls);
+        }
+
+        graph.insert(id, calls);
+    }
+
+    let mut found: HashSet<&str> = all_rules.iter()
+        .filter(|(_, r)| matches!(r.branch_fn, Some(BranchFn::Context(_))))
+        .map(|(id, _)| id.as_str())
+        .collect();
+
+    loop {
+        let more: Vec<&str> = graph.iter()
+            .filter(|(id, calls)| !found.contains(*id) && calls.iter().any(|x| found.contains(x)))
+            .map(|(id, _)| *id)
+            .collect();
+
+        if more.is_empty() {
+            return found.into_iter().map(String::from).collect();
+        }
+
+        found.extend(more);
+    }
+}
+
+// The rules a sentence calls anywhere in it.
+fn all_calls<'a>(sentence: &'a [Clause], ws_calls: &[&'a str], calls: &mut Vec<&'a str>) {
+    for clause in sentence {
+        match clause {
+            Clause::AnyOf { ref sentences, .. } => {
+                for x in sentences {
+                    all_calls(x, ws_calls, calls);
+                }
+            },
+            Clause::Id { ref name, .. } => {
+                calls.push(name);
+            },
+            Clause::Whitespace { .. } => {
+                calls.extend_from_slice(ws_calls);
+            },
+            _ => {},
+        }
+    }
+}
+
+// The rules a sentence calls at the position it starts at.
+fn left_calls<'a>(sentence: &'a [Clause], nullable: &Nullable, ws_calls: &[&'a str], calls: &mut Vec<&'a str>) {
+    for clause in sentence {
+        match clause {
+            Clause::AnyOf { ref sentences, .. } => {
+                for x in sentences {
+                    left_calls(x, nullable, ws_calls, calls);
+                }
+            },
+            Clause::Id { ref name, .. } => {
+                calls.push(name);
+            },
+            Clause::Whitespace { .. } => {
+                calls.extend_from_slice(ws_calls);
+            },
+            _ => {},
+        }
+
+        if !nullable.clause(clause) {
+            return;
+        }
+    }
+}
+
+fn find_cycles<'a>(id: &'a str, graph: &HashMap<&'a str, Vec<&'a str>>, path: &mut Vec<&'a str>, done: &mut HashSet<&'a str>, cycles: &mut BTreeSet<Vec<String>>) {
+    if let Some(i) = path.iter().position(|x| *x == id) {
+        // Start the cycle at its smallest id, so the same cycle found from another rule is only reported once.
+        let mut cycle: Vec<&str> = path[i..].to_vec();
+        let min = (0..cycle.len()).min_by_key(|x| cycle[*x]).unwrap();
+        cycle.rotate_left(min);
+        cycle.push(cycle[0]);
+        cycles.insert(cycle.into_iter().map(String::from).collect());
+        return;
+    }
+
+    if done.contains(id) {
+        return;
+    }
+
+    path.push(id);
+
+    for next in &graph[id] {
+        find_cycles(next, graph, path, done, cycles);
+    }
+
+    path.pop();
+    done.insert(id);
+}
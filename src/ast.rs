@@ -2,6 +2,10 @@
 // Licensed under the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>
 // This file may not be copied, modified, or distributed except according to those terms.
 
+use regex_syntax::hir::{Class, ClassUnicode, ClassUnicodeRange, Hir, HirKind};
+use regex_syntax::Parser;
+use std::cmp::Ordering;
+
 #[derive(Clone, Debug)]
 pub struct AlterText { 
     pub find: String, 
@@ -14,6 +18,23 @@ pub struct CharRange {
     pub end: char,
 }
 
+// A Unicode general category, script or binary property like `\p{L}` or `\P{Greek}`. The ranges are sorted and do
+// not overlap.
+#[derive(Clone, Debug)]
+pub struct CharClass {
+    pub name: String,
+    pub negated: bool,
+    pub ranges: Vec<CharRange>,
+}
+
+// A clause that only looks ahead, it matches without consuming any input. `!` matches when the clause does not match
+// and `&` when it does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lookahead {
+    Not,
+    And,
+}
+
 #[derive(Debug)]
 pub enum ParseData {
     AlterText { find: String, replace: String },
@@ -21,16 +42,18 @@ pub enum ParseData {
     AnyChar,
     AnyCharExcept(Vec<char>),
     AnyOf(Vec<Vec<Clause>>),
+    Char(char),
+    CharClass(CharClass),
     CharRange { start: char, end: char },
-    CharRanges(Vec<CharRange>),
+    CharRanges { ranges: Vec<CharRange>, classes: Vec<CharClass> },
     Clause(Clause),
     Clauses(Vec<Clause>),
     Eof,
     Integer(u64),
-    Id(String),
+    Id { name: String, capture: Option<String> },
     Literal(String),
+    Lookahead(Lookahead),
     NoBacktrack(String),
-    Not,
     Range { min: u64, max: u64 },
     Text(String),
     Whitespace { min: u64, max: u64 },
@@ -38,43 +61,112 @@ pub enum ParseData {
 
 #[derive(Clone, Debug)]
 pub enum Clause {
-    AlterTexts { replacements: Vec<AlterText>, min: u64, max: u64 },
-    AnyChar { not: bool, min: u64, max: u64 },
-    AnyCharExcept { not: bool, chars: Vec<char>, min: u64, max: u64 },
-    AnyOf { not: bool, sentences: Vec<Vec<Clause>>, min: u64, max: u64 },
-    CharRanges { not: bool, ranges: Vec<CharRange>, min: u64, max: u64 },
+    AlterTexts { replacements: Vec<AlterText>, ignore_case: bool, min: u64, max: u64 },
+    AnyChar { lookahead: Option<Lookahead>, min: u64, max: u64 },
+    // `folded` is set when the clause ignores case, it is all the chars excepted and their case variants.
+    AnyCharExcept { lookahead: Option<Lookahead>, chars: Vec<char>, folded: Option<Vec<CharRange>>, min: u64, max: u64 },
+    AnyOf { lookahead: Option<Lookahead>, sentences: Vec<Vec<Clause>>, min: u64, max: u64 },
+    // `folded` is set when the clause ignores case, it is all the chars of the clause and their case variants.
+    CharRanges { lookahead: Option<Lookahead>, ranges: Vec<CharRange>, classes: Vec<CharClass>, folded: Option<Vec<CharRange>>, min: u64, max: u64 },
     Eof,
-    Id { not: bool, name: String, min: u64, max: u64 },
-    Literal { not: bool, text: String, min: u64, max: u64 },
+    // `capture` is the name of the values of the rule, as in `<lhs:expr>`.
+    Id { lookahead: Option<Lookahead>, name: String, capture: Option<String>, min: u64, max: u64 },
+    Literal { lookahead: Option<Lookahead>, text: String, ignore_case: bool, min: u64, max: u64 },
     NoBacktrack(String),
     Whitespace { min: u64, max: u64 },
 }
 
-impl From<(bool, ParseData, ParseData)> for Clause {
-    fn from(val: (bool, ParseData, ParseData)) -> Self {
+impl From<(Option<Lookahead>, ParseData, ParseData)> for Clause {
+    fn from(val: (Option<Lookahead>, ParseData, ParseData)) -> Self {
         match val {
-            (false, ParseData::AlterTexts(replacements), ParseData::Range { min, max }) => Clause::AlterTexts { replacements, min, max },
-            (not, ParseData::AnyChar, ParseData::Range { min, max }) => Clause::AnyChar { not, min, max },
-            (not, ParseData::AnyCharExcept(chars), ParseData::Range { min, max }) => Clause::AnyCharExcept { not, chars, min, max },
-            (not, ParseData::AnyOf(sentences), ParseData::Range { min, max }) => Clause::AnyOf { not, sentences, min, max },
-            (not, ParseData::CharRanges(ranges), ParseData::Range { min, max }) => Clause::CharRanges { not, ranges, min, max },
-            (false, ParseData::Eof, ParseData::Range { min: 1, max: 1 }) => Clause::Eof,
-            (not, ParseData::Id(name), ParseData::Range { min, max }) => Clause::Id { not, name, min, max },
-            (not, ParseData::Literal(text), ParseData::Range { min, max }) => Clause::Literal { not, text, min, max },
-            (false, ParseData::NoBacktrack(err_msg), ParseData::Range { min: 1, max: 1 }) => Clause::NoBacktrack(err_msg),
-            (false, ParseData::Whitespace { min, max: ::std::u64::MAX }, _) => Clause::Whitespace { min, max: ::std::u64::MAX },
-            (not, clause, range) => unreachable!("Unexpected match of\n- not: {},\n- clause: {:?}\n- range: {:?}", not, clause, range)
+            (None, ParseData::AlterTexts(replacements), ParseData::Range { min, max }) => Clause::AlterTexts { replacements, ignore_case: false, min, max },
+            (lookahead, ParseData::AnyChar, ParseData::Range { min, max }) => Clause::AnyChar { lookahead, min, max },
+            (lookahead, ParseData::AnyCharExcept(chars), ParseData::Range { min, max }) => Clause::AnyCharExcept { lookahead, chars, folded: None, min, max },
+            (lookahead, ParseData::AnyOf(sentences), ParseData::Range { min, max }) => Clause::AnyOf { lookahead, sentences, min, max },
+            (lookahead, ParseData::CharClass(class), ParseData::Range { min, max }) => Clause::CharRanges { lookahead, ranges: vec![], classes: vec![class], folded: None, min, max },
+            (lookahead, ParseData::CharRanges { ranges, classes }, ParseData::Range { min, max }) => Clause::CharRanges { lookahead, ranges, classes, folded: None, min, max },
+            (None, ParseData::Eof, ParseData::Range { min: 1, max: 1 }) => Clause::Eof,
+            (lookahead, ParseData::Id { name, capture }, ParseData::Range { min, max }) => Clause::Id { lookahead, name, capture, min, max },
+            (lookahead, ParseData::Literal(text), ParseData::Range { min, max }) => Clause::Literal { lookahead, text, ignore_case: false, min, max },
+            (None, ParseData::NoBacktrack(err_msg), ParseData::Range { min: 1, max: 1 }) => Clause::NoBacktrack(err_msg),
+            (None, ParseData::Whitespace { min, max: u64::MAX }, _) => Clause::Whitespace { min, max: u64::MAX },
+            (lookahead, clause, range) => unreachable!("Unexpected match of\n- lookahead: {:?},\n- clause: {:?}\n- range: {:?}", lookahead, clause, range)
         }
     }
 }
 
-impl ParseData {
-    pub fn is_not(&self) -> bool {
+impl Clause {
+    // Makes the literals, char ranges and alter clauses of the clause, also those in groups, ignore case.
+    pub fn ignore_case(&mut self) {
         match self {
-            ParseData::Not => true,
-            _ => false,
+            Clause::AlterTexts { ignore_case, .. } => *ignore_case = true,
+            Clause::AnyCharExcept { chars, folded, .. } => {
+                *folded = Some(case_fold(chars.iter().map(|&c| (c, c))));
+            },
+            Clause::AnyOf { sentences, .. } => sentences.iter_mut().flatten().for_each(Clause::ignore_case),
+            Clause::CharRanges { ranges, classes, folded, .. } => {
+                let all = ranges.iter().chain(classes.iter().flat_map(|x| &x.ranges));
+                *folded = Some(case_fold(all.map(|x| (x.start, x.end))));
+            },
+            Clause::Literal { ignore_case, .. } => *ignore_case = true,
+            _ => {},
         }
     }
+}
+
+// The sorted ranges of the chars from `start` to `end` of every range and all their simple case foldings.
+fn case_fold<I>(ranges: I) -> Vec<CharRange>
+where I: Iterator<Item = (char, char)> {
+    let mut class = ClassUnicode::new(ranges.map(|(start, end)| ClassUnicodeRange::new(start, end)));
+    class.case_fold_simple();
+    class.ranges().iter().map(|x| CharRange { start: x.start(), end: x.end() }).collect()
+}
+
+impl CharClass {
+    pub fn new(name: &str, negated: bool) -> Result<Self, String> {
+        let mut class = match Parser::new().parse(&format!("\\p{{{}}}", name)).map(Hir::into_kind) {
+            Ok(HirKind::Class(Class::Unicode(class))) => class,
+            _ => return Err(format!("Unknown Unicode property \"{}\".", name)),
+        };
+
+        if negated {
+            class.negate();
+        }
+
+        let ranges = class.ranges()
+            .iter()
+            .map(|x| CharRange { start: x.start(), end: x.end() })
+            .collect();
+
+        Ok(CharClass { name: String::from(name), negated, ranges })
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        sorted_ranges_contain(&self.ranges, c)
+    }
+}
+
+// Whether `c` is in one of `ranges`, which must be sorted and must not overlap.
+pub fn sorted_ranges_contain(ranges: &[CharRange], c: char) -> bool {
+    ranges
+        .binary_search_by(|x| {
+            if x.end < c {
+                Ordering::Less
+            }
+            else if x.start > c {
+                Ordering::Greater
+            }
+            else {
+                Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+impl ParseData {
+    pub fn is_lookahead(&self) -> bool {
+        matches!(self, ParseData::Lookahead(_))
+    }
 
     pub fn unwrap_alter_text(self) -> AlterText {
         match self {
@@ -83,6 +175,13 @@ impl ParseData {
         }
     }
 
+    pub fn unwrap_char(self) -> char {
+        match self {
+            ParseData::Char(value) => value,
+            _ => panic!("Not a ParseData::Char."),
+        }
+    }
+
     pub fn unwrap_char_range(self) -> CharRange {
         match self {
             ParseData::CharRange { start, end } => CharRange { start, end },
@@ -111,6 +210,13 @@ impl ParseData {
         }
     }
 
+    pub fn unwrap_lookahead(self) -> Lookahead {
+        match self {
+            ParseData::Lookahead(value) => value,
+            _ => panic!("Not a ParseData::Lookahead."),
+        }
+    }
+
     pub fn unwrap_text(self) -> String {
         match self {
             ParseData::Text(value) => value,
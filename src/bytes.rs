@@ -0,0 +1,127 @@
+// Copyright (c) 2015-2023 Vincent van Ingen <code@abitvin.com>
+// Licensed under the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use super::ast::Clause;
+use super::error::{GrammarError, Position};
+use super::{CompiledGrammar, Grammar};
+
+/// A grammar over bytes instead of text, for binary or non-UTF-8 input like Latin-1. The expression language is the
+/// same, but every char of an expression stands for the byte of the same value: `[\x00-\x1F]` matches the control
+/// bytes and `\xE9` or `é` matches byte 0xE9. Chars above `\xFF` never match, a literal or alter replacement with one is
+/// rejected. The branch functions get the bytes of the match.
+pub struct ByteGrammar<T> {
+    grammar: Grammar<T>,
+}
+
+/// A byte grammar ready to scan. Error positions count bytes, so the offset and the column are in bytes.
+#[derive(Clone)]
+pub struct CompiledByteGrammar<T> {
+    grammar: CompiledGrammar<T>,
+}
+
+impl<T> Default for ByteGrammar<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ByteGrammar<T> {
+    pub fn new() -> Self {
+        Self { grammar: Grammar::new() }
+    }
+
+    pub fn new_with_ws(expr: &str) -> Self {
+        Self { grammar: Grammar::new_with_ws(expr) }
+    }
+
+    pub fn compile(self) -> Result<CompiledByteGrammar<T>, GrammarError> {
+        self.grammar.compile().map(|grammar| CompiledByteGrammar { grammar })
+    }
+
+    pub fn map(&mut self, id: &str, expr: &str, branch_fn: impl Fn(Vec<T>, &[u8]) -> Result<T, String> + Send + Sync + 'static) {
+        if let Err(err) = self.try_map(id, expr, branch_fn) {
+            panic!("{}", err);
+        }
+    }
+
+    pub fn rule(&mut self, id: &str, expr: &str) {
+        if let Err(err) = self.try_rule(id, expr) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Like `map`, but returns an error instead of panicking when `id` is already used or `expr` is not valid.
+    pub fn try_map(&mut self, id: &str, expr: &str, branch_fn: impl Fn(Vec<T>, &[u8]) -> Result<T, String> + Send + Sync + 'static) -> Result<(), GrammarError> {
+        self.grammar.try_map(id, expr, move |b, l| branch_fn(b, &encode(l)))?;
+        self.check(id)
+    }
+
+    /// Like `rule`, but returns an error instead of panicking when `id` is already used or `expr` is not valid.
+    pub fn try_rule(&mut self, id: &str, expr: &str) -> Result<(), GrammarError> {
+        self.grammar.try_rule(id, expr)?;
+        self.check(id)
+    }
+
+    // Removes rule `id` again when it would put a char above `\xFF` in the lexeme, it has no byte to encode to.
+    fn check(&mut self, id: &str) -> Result<(), GrammarError> {
+        match not_bytes(&self.grammar.rules[id].sentence) {
+            Some(text) => {
+                let text = String::from(text);
+                self.grammar.rules.remove(id);
+                Err(GrammarError::NotBytes { rule: String::from(id), text })
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T> CompiledByteGrammar<T> {
+    pub fn scan(&self, root_id: &str, code: &[u8]) -> Result<Vec<T>, GrammarError> {
+        let text = decode(code);
+        self.grammar.scan(root_id, &text).map_err(|err| err.map_position(|x| locate(&text, x)))
+    }
+
+    /// Like `CompiledGrammar::scan_prefix`, the number of bytes consumed is returned with the values.
+    pub fn scan_prefix(&self, root_id: &str, code: &[u8]) -> Result<(Vec<T>, usize), GrammarError> {
+        let text = decode(code);
+
+        match self.grammar.scan_prefix(root_id, &text) {
+            Ok((values, consumed)) => Ok((values, text[..consumed].chars().count())),
+            Err(err) => Err(err.map_position(|x| locate(&text, x))),
+        }
+    }
+}
+
+impl<T: Clone> CompiledByteGrammar<T> {
+    /// See `CompiledGrammar::memoize`.
+    pub fn memoize(&mut self, enabled: bool) {
+        self.grammar.memoize(enabled);
+    }
+}
+
+// Every byte becomes the char of the same value, so the text has a char for every byte.
+fn decode(code: &[u8]) -> String {
+    code.iter().map(|&x| char::from(x)).collect()
+}
+
+// The first literal or alter replacement of the sentence, also in groups, with a char above `\xFF`.
+fn not_bytes(sentence: &[Clause]) -> Option<&str> {
+    let is_bytes = |text: &str| text.chars().all(|x| x <= '\u{FF}');
+
+    sentence.iter().find_map(|clause| match clause {
+        Clause::Literal { text, .. } if !is_bytes(text) => Some(text.as_str()),
+        Clause::AlterTexts { replacements, .. } => replacements.iter().map(|x| x.replace.as_str()).find(|x| !is_bytes(x)),
+        Clause::AnyOf { sentences, .. } => sentences.iter().find_map(|x| not_bytes(x)),
+        _ => None,
+    })
+}
+
+fn encode(text: &str) -> Vec<u8> {
+    text.chars().map(|x| x as u8).collect()
+}
+
+// A position in the decoded text as a position in the bytes. A line and a column are the same in both.
+fn locate(text: &str, position: Position) -> Position {
+    Position { offset: text[..position.offset].chars().count(), ..position }
+}
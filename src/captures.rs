@@ -0,0 +1,73 @@
+// Copyright (c) 2015-2023 Vincent van Ingen <code@abitvin.com>
+// Licensed under the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use super::span::Span;
+use std::ops::Range;
+
+/// The named captures of a rule added with `Grammar::map_named`. A capture like `<lhs:expr>` names the values rule
+/// `expr` produced. A name used more than once, as in `<arg:expr>(,<arg:expr>)*`, has the values of all its matches
+/// in the order they were matched. Only the captures written in the expression of the rule itself are there, values
+/// that are not captured are dropped.
+pub struct Captures<'a, T> {
+    code: &'a str,
+    values: Vec<Option<T>>,
+    captures: Vec<(&'a str, Range<usize>, Span)>,
+}
+
+impl<'a, T> Captures<'a, T> {
+    pub(crate) fn new(code: &'a str, values: Vec<T>, captures: Vec<(&'a str, Range<usize>, Span)>) -> Self {
+        Self {
+            code,
+            values: values.into_iter().map(Some).collect(),
+            captures,
+        }
+    }
+
+    /// The first value captured by `name`, or `None` when `name` did not match or the value was taken.
+    pub fn get(&self, name: &str) -> Option<&T> {
+        self.get_all(name).into_iter().next()
+    }
+
+    /// All values captured by `name` that were not taken.
+    pub fn get_all(&self, name: &str) -> Vec<&T> {
+        self.indexes(name).into_iter().filter_map(|i| self.values[i].as_ref()).collect()
+    }
+
+    /// Like `get`, but moves the value out of the captures.
+    pub fn take(&mut self, name: &str) -> Option<T> {
+        self.indexes(name).into_iter().find_map(|i| self.values[i].take())
+    }
+
+    /// Like `get_all`, but moves the values out of the captures.
+    pub fn take_all(&mut self, name: &str) -> Vec<T> {
+        self.indexes(name).into_iter().filter_map(|i| self.values[i].take()).collect()
+    }
+
+    /// The text matched by the first capture of `name`.
+    pub fn text(&self, name: &str) -> Option<&'a str> {
+        self.texts(name).into_iter().next()
+    }
+
+    /// The text matched by every capture of `name`.
+    pub fn texts(&self, name: &str) -> Vec<&'a str> {
+        self.captures
+            .iter()
+            .filter(|x| x.0 == name)
+            .map(|x| &self.code[x.2.start..x.2.end])
+            .collect()
+    }
+
+    /// Whether `name` matched at all, also when it matched without producing a value.
+    pub fn contains(&self, name: &str) -> bool {
+        self.captures.iter().any(|x| x.0 == name)
+    }
+
+    fn indexes(&self, name: &str) -> Vec<usize> {
+        self.captures
+            .iter()
+            .filter(|x| x.0 == name)
+            .flat_map(|x| x.1.clone())
+            .collect()
+    }
+}
@@ -0,0 +1,238 @@
+// Copyright (c) 2015-2023 Vincent van Ingen <code@abitvin.com>
+// Licensed under the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+/// Something the scanner was looking for when it failed.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Expected {
+    AnyChar,
+    AnyCharExcept(Vec<char>),
+    CharRange { start: char, end: char },
+    CharClass { name: String, negated: bool },
+    Eof,
+    Literal(String),
+    Rule(String),
+    Whitespace,
+}
+
+/// A location in the scanned input. `line` and `column` start at 1 and the column is counted in chars.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A reference to a rule that was never added. `rule` is the referring rule, or `None` when it is the whitespace
+/// expression. `clause` is the index of the referring clause in that expression, starting at 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownRule {
+    pub id: String,
+    pub rule: Option<String>,
+    pub clause: usize,
+}
+
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum GrammarError {
+    /// Rule expressions refer to rules that were never added.
+    UnknownRules(Vec<UnknownRule>),
+    /// Rules can call themselves without consuming any input. Every cycle is the path of rule ids from a rule back
+    /// to itself.
+    LeftRecursion { cycles: Vec<Vec<String>> },
+    /// Operator `expr` of operators rule `rule` can match without consuming any input.
+    EmptyOperator { rule: String, expr: String },
+    /// The root id given to `scan` is not a rule of the grammar.
+    UnknownRoot { id: String },
+    /// The rule id given to `recover` is not a rule of the grammar.
+    UnknownRecovery { id: String },
+    /// The offset given to `scan_prefix_at` is past the end of the input or inside a char.
+    InvalidOffset { offset: usize },
+    /// Literal or alter replacement `text` of rule `rule` of a `ByteGrammar` has a char above `\xFF`.
+    NotBytes { rule: String, text: String },
+    /// A rule id was added more than once.
+    DuplicateRule { id: String },
+    /// The expression of rule `id` is not valid. The source is the parse error within the expression.
+    Syntax { id: String, source: Box<GrammarError> },
+    /// The input does not match the grammar.
+    Parse { position: Position, rule: Option<String>, expected: Vec<Expected> },
+    /// A branch function returned an error.
+    Branch { rule: String, msg: String },
+    /// The input failed to match after passing a no backtrack clause.
+    NoBacktrack { position: Position, rule: String, msg: String },
+    /// Reading the input failed, see `CompiledGrammar::scan_reader`.
+    Io { source: Arc<io::Error> },
+}
+
+impl GrammarError {
+    pub(crate) fn parse(position: Position, rule: Option<String>, mut expected: Vec<Expected>) -> Self {
+        expected.sort();
+        expected.dedup();
+        GrammarError::Parse { position, rule, expected }
+    }
+
+    // The error with its position, if it has one, changed by `f`.
+    pub(crate) fn map_position<F>(self, f: F) -> Self
+    where F: FnOnce(Position) -> Position {
+        match self {
+            GrammarError::Parse { position, rule, expected } => GrammarError::Parse { position: f(position), rule, expected },
+            GrammarError::NoBacktrack { position, rule, msg } => GrammarError::NoBacktrack { position: f(position), rule, msg },
+            other => other,
+        }
+    }
+
+    /// Where the input failed to match. For a syntax error this is the position within the rule expression.
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            GrammarError::Syntax { source, .. } => source.position(),
+            GrammarError::Parse { position, .. } => Some(*position),
+            GrammarError::NoBacktrack { position, .. } => Some(*position),
+            _ => None,
+        }
+    }
+
+    /// The rule in which the error occurred. For a parse error this is the innermost rule that was being attempted.
+    pub fn rule(&self) -> Option<&str> {
+        match self {
+            GrammarError::UnknownRules(_) => None,
+            GrammarError::LeftRecursion { .. } => None,
+            GrammarError::EmptyOperator { rule, .. } => Some(rule),
+            GrammarError::UnknownRoot { .. } => None,
+            GrammarError::UnknownRecovery { .. } => None,
+            GrammarError::InvalidOffset { .. } => None,
+            GrammarError::NotBytes { rule, .. } => Some(rule),
+            GrammarError::DuplicateRule { id } => Some(id),
+            GrammarError::Syntax { id, .. } => Some(id),
+            GrammarError::Parse { rule, .. } => rule.as_deref(),
+            GrammarError::Branch { rule, .. } => Some(rule),
+            GrammarError::NoBacktrack { rule, .. } => Some(rule),
+            GrammarError::Io { .. } => None,
+        }
+    }
+
+    /// Everything that would have allowed the scan to continue at the failure position.
+    pub fn expected(&self) -> &[Expected] {
+        match self {
+            GrammarError::Syntax { source, .. } => source.expected(),
+            GrammarError::Parse { expected, .. } => expected,
+            _ => &[],
+        }
+    }
+}
+
+impl Position {
+    /// The position of byte `offset` in `code`.
+    pub fn locate(code: &str, offset: usize) -> Self {
+        let before = &code[..offset];
+        let line = before.matches('\n').count() + 1;
+
+        let column = match before.rfind('\n') {
+            Some(i) => before[i + 1..].chars().count() + 1,
+            None => before.chars().count() + 1,
+        };
+
+        Position { offset, line, column }
+    }
+
+    // This position as if the input it is in came after input that ended at `base`.
+    pub(crate) fn after(self, base: Position) -> Self {
+        Position {
+            offset: base.offset + self.offset,
+            line: base.line + self.line - 1,
+            column: if self.line == 1 { base.column + self.column - 1 } else { self.column },
+        }
+    }
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expected::AnyChar => write!(f, "any character"),
+            Expected::AnyCharExcept(chars) => write!(f, "[^{}]", chars.iter().collect::<String>()),
+            Expected::CharRange { start, end } => write!(f, "[{}-{}]", start, end),
+            Expected::CharClass { name, negated } => write!(f, "\\{}{{{}}}", if *negated { "P" } else { "p" }, name),
+            Expected::Eof => write!(f, "end of input"),
+            Expected::Literal(text) => write!(f, "{:?}", text),
+            Expected::Rule(id) => write!(f, "<{}>", id),
+            Expected::Whitespace => write!(f, "whitespace"),
+        }
+    }
+}
+
+impl fmt::Display for UnknownRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.rule {
+            Some(ref rule) => write!(f, "Rule \"{}\" used in clause {} of rule \"{}\" not found.", self.id, self.clause, rule),
+            None => write!(f, "Rule \"{}\" used in clause {} of the whitespace not found.", self.id, self.clause),
+        }
+    }
+}
+
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GrammarError::UnknownRules(unknown) => {
+                for (i, x) in unknown.iter().enumerate() {
+                    write!(f, "{}{}", if i == 0 { "" } else { "\n" }, x)?;
+                }
+
+                Ok(())
+            },
+            GrammarError::LeftRecursion { cycles } => {
+                for (i, x) in cycles.iter().enumerate() {
+                    write!(f, "{}Rule \"{}\" is left recursive: {}.", if i == 0 { "" } else { "\n" }, x[0], x.join(" -> "))?;
+                }
+
+                Ok(())
+            },
+            GrammarError::EmptyOperator { rule, expr } => write!(f, "Operator \"{}\" of rule \"{}\" can match empty input.", expr, rule),
+            GrammarError::UnknownRoot { id } => write!(f, "Rule \"{}\" not found.", id),
+            GrammarError::UnknownRecovery { id } => write!(f, "Rule \"{}\" to recover not found.", id),
+            GrammarError::InvalidOffset { offset } => write!(f, "Offset {} is not at a char of the input.", offset),
+            GrammarError::NotBytes { rule, text } => write!(f, "Text \"{}\" of rule \"{}\" has a char above \\xFF.", text, rule),
+            GrammarError::DuplicateRule { id } => write!(f, "The rule \"{}\" already used.", id),
+            GrammarError::Syntax { id, source } => write!(f, "Error parsing rule \"{}\": {}", id, source),
+            GrammarError::Parse { position, rule, expected } => {
+                write!(f, "Parse error at line {}, column {}", position.line, position.column)?;
+
+                if let Some(rule) = rule {
+                    write!(f, " in rule \"{}\"", rule)?;
+                }
+
+                if let Some((last, rest)) = expected.split_last() {
+                    write!(f, ", expected ")?;
+
+                    for (i, x) in rest.iter().enumerate() {
+                        write!(f, "{}{}", if i == 0 { "" } else { ", " }, x)?;
+                    }
+
+                    if !rest.is_empty() {
+                        write!(f, " or ")?;
+                    }
+
+                    write!(f, "{}", last)?;
+                }
+
+                write!(f, ".")
+            },
+            GrammarError::Branch { msg, .. } => write!(f, "{}", msg),
+            GrammarError::NoBacktrack { msg, .. } => write!(f, "{}", msg),
+            GrammarError::Io { source } => write!(f, "Error reading the input: {}", source),
+        }
+    }
+}
+
+impl Error for GrammarError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GrammarError::Syntax { source, .. } => Some(source.as_ref()),
+            GrammarError::Io { source } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
@@ -2,45 +2,109 @@
 // Licensed under the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>
 // This file may not be copied, modified, or distributed except according to those terms.
 
-extern crate rule;
-
+mod analysis;
 mod ast;
+mod bytes;
+mod captures;
+mod error;
+mod operators;
+mod reader;
 mod rules;
-
+mod scanner;
+mod search;
+mod span;
+mod tree;
+
+pub use bytes::{ByteGrammar, CompiledByteGrammar};
+pub use captures::Captures;
+pub use error::{Expected, GrammarError, Position, UnknownRule};
+pub use operators::{Assoc, Operators};
+pub use reader::ScanReader;
+pub use scanner::ScanStats;
+pub use search::{FindIter, Match};
+pub use span::Span;
+pub use tree::Node;
+
+use analysis::LeftRecursive;
 use ast::{Clause, ParseData};
-use rule::{Rule, RuleError};
+use operators::OperatorTable;
 use rules::root;
-use std::collections::HashMap;
-use std::error::Error;
-use std::fmt;
+use scanner::Scanner;
+use std::collections::{HashMap, HashSet};
+use std::io::BufRead;
+use std::sync::Arc;
+
+const VERBOSE: &str = "(?x)";
+
+type PlainFn<T> = dyn Fn(Vec<T>, &str) -> Result<T, String> + Send + Sync;
+type SpannedFn<T> = dyn Fn(Vec<(T, Span)>, &str, Span) -> Result<T, String> + Send + Sync;
+type ContextFn<T, C> = dyn Fn(Vec<T>, &str, &mut C) -> Result<T, String> + Send + Sync;
+type NamedFn<T> = dyn Fn(Captures<T>, &str) -> Result<T, String> + Send + Sync;
+type ErrorFn<T> = dyn Fn(&str) -> T + Send + Sync;
+
+enum BranchFn<T, C> {
+    Plain(Arc<PlainFn<T>>),
+    Spanned(Arc<SpannedFn<T>>),
+    Context(Arc<ContextFn<T, C>>),
+    Named(Arc<NamedFn<T>>),
+}
 
-struct GrammarRule<T> { 
-    rule: Rule<T>,
+impl<T, C> Clone for BranchFn<T, C> {
+    fn clone(&self) -> Self {
+        match self {
+            BranchFn::Plain(x) => BranchFn::Plain(Arc::clone(x)),
+            BranchFn::Spanned(x) => BranchFn::Spanned(Arc::clone(x)),
+            BranchFn::Context(x) => BranchFn::Context(Arc::clone(x)),
+            BranchFn::Named(x) => BranchFn::Named(Arc::clone(x)),
+        }
+    }
+}
+
+// What a rule does when it fails while scanning with recovery, see `Grammar::recover`.
+struct Recovery<T> {
     sentence: Vec<Clause>,
+    error_fn: Arc<ErrorFn<T>>,
 }
 
-type CompiledGrammarRules<T> = HashMap<String, Rule<T>>;
-type GrammarRules<T> = HashMap<String, GrammarRule<T>>;
+impl<T> Clone for Recovery<T> {
+    fn clone(&self) -> Self {
+        Recovery { sentence: self.sentence.clone(), error_fn: Arc::clone(&self.error_fn) }
+    }
+}
 
 #[derive(Clone)]
-pub struct CompiledGrammar<T> {
-    rules: CompiledGrammarRules<T>,
+struct GrammarRule<T, C = ()> { 
+    branch_fn: Option<BranchFn<T, C>>,
+    sentence: Vec<Clause>,
+    left_recursive: Option<LeftRecursive>,
+    operators: Option<Arc<OperatorTable<T>>>,
+    recovery: Option<Recovery<T>>,
 }
 
-pub struct Grammar<T> {
-    rules: GrammarRules<T>,
-    parser: Rule<ParseData>,
-    ws: GrammarRule<T>,
+type GrammarRules<T, C = ()> = HashMap<String, GrammarRule<T, C>>;
+
+/// A grammar ready to scan. `C` is the type of the context the branch functions of `Grammar::map_with_context` get.
+/// A compiled grammar is `Send` and `Sync`, so it can be shared by threads that scan at the same time.
+#[derive(Clone)]
+pub struct CompiledGrammar<T, C = ()> {
+    rules: GrammarRules<T, C>,
+    ws: Vec<Clause>,
+    memo_clone: Option<fn(&T) -> T>,
+    context_rules: HashSet<String>,
 }
 
-#[derive(Debug)]
-pub struct GrammarError {
-    msg: String,
+/// The rules to compile into a `CompiledGrammar`. In a rule expression `&x` matches where `x` matches and `!x`
+/// where it does not, neither consumes input. So `&` is not a literal char, `R&D` is `R` followed by a lookahead of
+/// `D` and no longer matches the text `R&D`, write `R\&D` for that.
+pub struct Grammar<T, C = ()> {
+    rules: GrammarRules<T, C>,
+    parser: GrammarRules<ParseData>,
+    ws: Vec<Clause>,
 }
 
-impl<T> Grammar<T> {
+impl<T, C> Grammar<T, C> {
     pub fn new() -> Self {
-        Self::new_("(\\ |\t|\n|\r)")
+        Self::new_("(\\ |\\t|\\n|\\r)")
     }
 
     pub fn new_with_ws(expr: &str) -> Self {
@@ -49,11 +113,7 @@ impl<T> Grammar<T> {
 
     fn new_(ws_expr: &str) -> Self {
         let parser = root();
-        
-        let ws = GrammarRule {
-            rule: Rule::default(),
-            sentence: parse(&parser, &ws_expr).unwrap(),
-        };
+        let ws = parse(&parser, ws_expr).unwrap();
 
         Self {
             rules: HashMap::new(),
@@ -62,223 +122,444 @@ impl<T> Grammar<T> {
         }
     }
 
-    pub fn compile(self) -> Result<CompiledGrammar<T>, GrammarError> {
-        let dummy = Rule::default();
-        self.ws.code_gen(&self.rules, &dummy)?;
+    pub fn compile(mut self) -> Result<CompiledGrammar<T, C>, GrammarError> {
+        let mut unknown = vec![];
+        unknown_rules(None, &self.ws, &self.rules, &mut unknown);
         
-        for (_, r) in &self.rules {
-            r.code_gen(&self.rules, &self.ws.rule)?;
+        for (id, r) in &self.rules {
+            unknown_rules(Some(id), &r.sentence, &self.rules, &mut unknown);
+
+            if let Some(ref recovery) = r.recovery {
+                unknown_rules(Some(id), &recovery.sentence, &self.rules, &mut unknown);
+            }
+        }
+
+        if !unknown.is_empty() {
+            unknown.sort_by(|a, b| (&a.rule, a.clause).cmp(&(&b.rule, b.clause)));
+            return Err(GrammarError::UnknownRules(unknown));
+        }
+
+        for (id, r) in self.rules.iter_mut().filter(|(_, r)| r.operators.is_none()) {
+            r.left_recursive = LeftRecursive::new(id, &r.sentence);
         }
-        
-        let mut rules = HashMap::new();
 
-        for (k, r) in self.rules {
-            rules.insert(k, r.rule);
+        if let Some((rule, expr)) = analysis::empty_operators(&self.rules, &self.ws).into_iter().next() {
+            return Err(GrammarError::EmptyOperator { rule, expr });
         }
 
-        Ok(CompiledGrammar { rules })
+        let cycles = analysis::left_recursion(&self.rules, &self.ws);
+
+        if !cycles.is_empty() {
+            return Err(GrammarError::LeftRecursion { cycles });
+        }
+
+        let context_rules = analysis::context_rules(&self.rules, &self.ws);
+
+        Ok(CompiledGrammar { 
+            rules: self.rules, 
+            ws: self.ws,
+            memo_clone: None,
+            context_rules,
+        })
     }
 
-    pub fn map(&mut self, id: &str, expr: &str, branch_fn: impl Fn(Vec<T>, &str) -> Result<T, String> + 'static) {
-        self.add(id, expr, Rule::new(branch_fn));
+    pub fn map(&mut self, id: &str, expr: &str, branch_fn: impl Fn(Vec<T>, &str) -> Result<T, String> + Send + Sync + 'static) {
+        if let Err(err) = self.try_map(id, expr, branch_fn) {
+            panic!("{}", err);
+        }
     }
     
     pub fn rule(&mut self, id: &str, expr: &str) {
-        self.add(id, expr, Rule::default());
+        if let Err(err) = self.try_rule(id, expr) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Like `rule`, but the literals, char ranges and alter clauses of `expr` ignore case, as if all of `expr` was in a
+    /// `(?i:...)` group. The rules it refers to are not affected.
+    pub fn rule_case_insensitive(&mut self, id: &str, expr: &str) {
+        if let Err(err) = self.try_rule_case_insensitive(id, expr) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Like `map`, but the branch function also gets the span of every value and the span of the rule match.
+    pub fn map_with_span(&mut self, id: &str, expr: &str, branch_fn: impl Fn(Vec<(T, Span)>, &str, Span) -> Result<T, String> + Send + Sync + 'static) {
+        if let Err(err) = self.try_map_with_span(id, expr, branch_fn) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Like `map`, but the branch function also gets the context given to `CompiledGrammar::scan_with`.
+    pub fn map_with_context(&mut self, id: &str, expr: &str, branch_fn: impl Fn(Vec<T>, &str, &mut C) -> Result<T, String> + Send + Sync + 'static) {
+        if let Err(err) = self.try_map_with_context(id, expr, branch_fn) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Like `map`, but the branch function gets the named captures of `expr` instead of all values, e.g. `lhs` and `rhs`
+    /// of `"<lhs:expr> \\+ <rhs:term>"`.
+    pub fn map_named(&mut self, id: &str, expr: &str, branch_fn: impl Fn(Captures<T>, &str) -> Result<T, String> + Send + Sync + 'static) {
+        if let Err(err) = self.try_map_named(id, expr, branch_fn) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Adds a rule that scans `operand_rule` values joined by the operators of `table`, combining them by precedence
+    /// and associativity. The operand rule must produce exactly one value.
+    pub fn operators(&mut self, id: &str, operand_rule: &str, table: Operators<T>) {
+        if let Err(err) = self.try_operators(id, operand_rule, table) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Like `map_with_context`, but returns an error instead of panicking when `id` is already used or `expr` is not
+    /// valid.
+    pub fn try_map_with_context(&mut self, id: &str, expr: &str, branch_fn: impl Fn(Vec<T>, &str, &mut C) -> Result<T, String> + Send + Sync + 'static) -> Result<(), GrammarError> {
+        self.add(id, expr, Some(BranchFn::Context(Arc::new(branch_fn))))
+    }
+
+    /// Lets rule `id` recover when it fails while scanning with `CompiledGrammar::scan_recover` or
+    /// `CompiledGrammar::parse_tree_recover`. The input is skipped up to and including the next match of `sync_expr`,
+    /// or up to the end of the input when there is none, and the rule matches with the value `error_fn` makes of the
+    /// skipped text. Why the rule failed is kept as a diagnostic. A rule only recovers where the scan fails without
+    /// it, so a rule that fails at the end of a repetition or in one of more alternatives does not. A rule that fails at
+    /// the end of the input does not recover either, so `sync_expr` must match something like `;` or `\n` to skip a
+    /// failed statement.
+    pub fn recover(&mut self, id: &str, sync_expr: &str, error_fn: impl Fn(&str) -> T + Send + Sync + 'static) {
+        if let Err(err) = self.try_recover(id, sync_expr, error_fn) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Like `recover`, but returns an error instead of panicking when rule `id` does not exist or `sync_expr` is not
+    /// valid.
+    pub fn try_recover(&mut self, id: &str, sync_expr: &str, error_fn: impl Fn(&str) -> T + Send + Sync + 'static) -> Result<(), GrammarError> {
+        let sentence = parse(&self.parser, sync_expr)
+            .map_err(|err| GrammarError::Syntax { id: String::from(id), source: Box::new(err) })?;
+
+        match self.rules.get_mut(id) {
+            Some(r) => {
+                r.recovery = Some(Recovery { sentence, error_fn: Arc::new(error_fn) });
+                Ok(())
+            },
+            None => Err(GrammarError::UnknownRecovery { id: String::from(id) }),
+        }
+    }
+
+    /// Like `map`, but returns an error instead of panicking when `id` is already used or `expr` is not valid.
+    pub fn try_map(&mut self, id: &str, expr: &str, branch_fn: impl Fn(Vec<T>, &str) -> Result<T, String> + Send + Sync + 'static) -> Result<(), GrammarError> {
+        self.add(id, expr, Some(BranchFn::Plain(Arc::new(branch_fn))))
+    }
+
+    /// Like `map_named`, but returns an error instead of panicking when `id` is already used or `expr` is not valid.
+    pub fn try_map_named(&mut self, id: &str, expr: &str, branch_fn: impl Fn(Captures<T>, &str) -> Result<T, String> + Send + Sync + 'static) -> Result<(), GrammarError> {
+        self.add(id, expr, Some(BranchFn::Named(Arc::new(branch_fn))))
+    }
+
+    /// Like `map_with_span`, but returns an error instead of panicking when `id` is already used or `expr` is not
+    /// valid.
+    pub fn try_map_with_span(&mut self, id: &str, expr: &str, branch_fn: impl Fn(Vec<(T, Span)>, &str, Span) -> Result<T, String> + Send + Sync + 'static) -> Result<(), GrammarError> {
+        self.add(id, expr, Some(BranchFn::Spanned(Arc::new(branch_fn))))
+    }
+
+    /// Like `rule`, but returns an error instead of panicking when `id` is already used or `expr` is not valid.
+    pub fn try_rule(&mut self, id: &str, expr: &str) -> Result<(), GrammarError> {
+        self.add(id, expr, None)
+    }
+
+    /// Like `rule_case_insensitive`, but returns an error instead of panicking when `id` is already used or `expr` is
+    /// not valid.
+    pub fn try_rule_case_insensitive(&mut self, id: &str, expr: &str) -> Result<(), GrammarError> {
+        self.add(id, expr, None)?;
+        self.rules.get_mut(id).unwrap().sentence.iter_mut().for_each(Clause::ignore_case);
+        Ok(())
+    }
+
+    /// Like `operators`, but returns an error instead of panicking when `id` is already used or an operator is not
+    /// valid.
+    pub fn try_operators(&mut self, id: &str, operand_rule: &str, table: Operators<T>) -> Result<(), GrammarError> {
+        if self.rules.contains_key(id) {
+            return Err(GrammarError::DuplicateRule { id: String::from(id) });
+        }
+
+        let parser = &self.parser;
+        let table = table.into_table(operand_rule, |expr| parse(parser, expr))
+            .map_err(|err| GrammarError::Syntax { id: String::from(id), source: Box::new(err) })?;
+
+        let gram_rule = GrammarRule {
+            branch_fn: None, sentence: table.sentence(), left_recursive: None, operators: Some(Arc::new(table)), recovery: None,
+        };
+
+        self.rules.insert(String::from(id), gram_rule);
+        Ok(())
     }
 
-    fn add(&mut self, id: &str, expr: &str, rule: Rule<T>) {
+    fn add(&mut self, id: &str, expr: &str, branch_fn: Option<BranchFn<T, C>>) -> Result<(), GrammarError> {
+        if self.rules.contains_key(id) {
+            return Err(GrammarError::DuplicateRule { id: String::from(id) });
+        }
+
         match parse(&self.parser, expr) {
             Ok(sentence) => {
                 let gram_rule = GrammarRule {
-                    rule, sentence,
+                    branch_fn, sentence, left_recursive: None, operators: None, recovery: None,
                 };
 
-                if self.rules.insert(String::from(id), gram_rule).is_some() {
-                    panic!("The rule \"{}\" already used.", id);
-                }
+                self.rules.insert(String::from(id), gram_rule);
+                Ok(())
             },
             Err(err) => {
-                panic!("Error parsing rule \"{}\": {:?}", id, err)
+                Err(GrammarError::Syntax { id: String::from(id), source: Box::new(err) })
             },
         }
     }
 }
 
-impl<T> CompiledGrammar<T> {
-    pub fn scan(&self, root_id: &str, code: &str) -> Result<Vec<T>, GrammarError> {
-        if let Some(root) = &self.rules.get(root_id) {
-            root.scan(code)
-                .map_err(|e| GrammarError::from(e))
-        }
-        else {
-            return Err(GrammarError::from(format!("Rule \"{}\" not found.", root_id)));
-        }   
+impl<T, C> CompiledGrammar<T, C> {
+    /// Scans `code` with a default context.
+    pub fn scan(&self, root_id: &str, code: &str) -> Result<Vec<T>, GrammarError>
+    where C: Default {
+        self.scan_with(root_id, code, &mut C::default())
     }
-}
 
-fn parse(parser: &Rule<ParseData>, expr: &str) -> Result<Vec<Clause>, RuleError> {
-    parser.scan(expr)
-        .map(|parse_data| parse_data.into_iter().map(|x| x.unwrap_clause()).collect())
-}
+    /// Scans `code`, the branch functions of `Grammar::map_with_context` get `ctx`.
+    pub fn scan_with(&self, root_id: &str, code: &str, ctx: &mut C) -> Result<Vec<T>, GrammarError> {
+        self.scanner(root_id, code, Some(ctx))?.scan(root_id)
+    }
 
-impl<T> GrammarRule<T> {
-    fn code_gen(&self, all_rules: &GrammarRules<T>, ws: &Rule<T>) -> Result<(), GrammarError> {
-        let is_one = self.sentence.len() == 1;
-        
-        for clause in &self.sentence {
-            let target = if is_one {
-                self.rule.clone()
-            }
-            else {
-                Rule::default()
-            };
+    /// Scans `code` into a tree of the rules that matched, the branch functions are not called.
+    pub fn parse_tree(&self, root_id: &str, code: &str) -> Result<Node, GrammarError> {
+        self.scanner(root_id, code, None)?.parse_tree(root_id)
+    }
 
-            match clause {
-                Clause::AlterTexts { ref replacements, min, max } => {
-                    let replacements = replacements.iter()
-                        .map(|x| (x.find.clone(), x.replace.clone()))
-                        .collect();
+    /// Scans `code` and recovers from failures of the rules set up with `Grammar::recover`. Returns the values of the
+    /// scan and the errors of all failures, the values are empty when the scan failed after all.
+    pub fn scan_recover(&self, root_id: &str, code: &str) -> (Vec<T>, Vec<GrammarError>)
+    where C: Default {
+        match self.scanner(root_id, code, Some(&mut C::default())) {
+            Ok(scanner) => scanner.scan_recover(root_id),
+            Err(err) => (vec![], vec![err]),
+        }
+    }
 
-                    add_extra(&target, false, *min, *max, |r: &Rule<T>| r.alter_string(replacements));
-                }
-                Clause::AnyChar { not, min, max } => {
-                    add_extra(&target, *not, *min, *max, |r: &Rule<T>| r.any_char());
-                },
-                Clause::AnyCharExcept { not, ref chars, min, max } => {
-                    add_extra(&target, *not, *min, *max, |r: &Rule<T>| r.any_char_except(chars.clone()));
-                },
-                Clause::AnyOf { not, ref sentences, min, max } => {
-                    let mut rules = vec![];
-
-                    for sentence in sentences {
-                        let gram_rule = GrammarRule {
-                            rule: Rule::default(),
-                            sentence: sentence.clone(),
-                        };
-
-                        gram_rule.code_gen(all_rules, ws)?;
-                        rules.push(gram_rule.rule);
-                    }
+    /// Like `parse_tree`, but recovers from failures like `scan_recover`. A rule that recovered becomes an error node.
+    pub fn parse_tree_recover(&self, root_id: &str, code: &str) -> (Option<Node>, Vec<GrammarError>) {
+        match self.scanner(root_id, code, None) {
+            Ok(scanner) => scanner.parse_tree_recover(root_id),
+            Err(err) => (None, vec![err]),
+        }
+    }
 
-                    let rules = rules.iter()
-                        .map(|x| x)
-                        .collect();
-                    
-                    add_extra(&target, *not, *min, *max, |r: &Rule<T>| r.any_of(rules));
-                },
-                Clause::CharRanges { not, ref ranges, min, max } => {
-                    let rules: Vec<Rule<_>> = ranges.iter()
-                        .map(|r| {
-                            let rule = Rule::default();
-                            rule.char_in(r.start, r.end);
-                            rule
-                        })
-                        .collect();
-                    
-                    let rules = rules.iter()
-                        .map(|x| x)
-                        .collect();
-
-                    add_extra(&target, *not, *min, *max, |r: &Rule<T>| r.any_of(rules));
-                },
-                Clause::Eof => {
-                    target.eof();
-                },
-                Clause::Id { not, ref name, min, max } => {
-                    let rule = match all_rules.get(name) {
-                        Some(ref r) => &r.rule,
-                        None => return Err(GrammarError::from(format!("Rule \"{}\" not found.", name)))
-                    };
-
-                    if *min == 1 && *max == 1 {
-                        if *not {
-                            target.not(&rule);
-                        }
-                        else {
-                            target.one(&rule);
-                        }
-                    }
-                    else {
-                        if *not {
-                            let quantity = Rule::default();
-                            quantity.between(*min, *max, &rule);
-                            target.not(&quantity);
-                        }
-                        else {
-                            target.between(*min, *max, &rule);
-                        }
-                    }
-                },
-                Clause::Literal { not, ref text, min, max } => {
-                    add_extra(&target, *not, *min, *max, |r: &Rule<T>| r.literal_string(text.clone()));
-                },
-                Clause::NoBacktrack(ref err_msg) => {
-                    target.no_backtrack(err_msg.clone());
-                },
-                Clause::Whitespace { min, max } => {
-                    target.between(*min, *max, &ws);
-                },
-            }
-            
-            if !is_one {
-                self.rule.one(&target);
-            }
+    /// Scans the start of `code` without requiring rule `root_id` to reach the end of it. Returns the values and the
+    /// number of bytes consumed, the next scan can continue from there with `scan_prefix_at`.
+    pub fn scan_prefix(&self, root_id: &str, code: &str) -> Result<(Vec<T>, usize), GrammarError>
+    where C: Default {
+        self.scan_prefix_at(root_id, code, 0)
+    }
+
+    /// Like `scan_prefix`, but scans from byte `offset` of `code`. Error positions are positions in all of `code`. The
+    /// offset must be at the start of a char or at the end of `code`.
+    pub fn scan_prefix_at(&self, root_id: &str, code: &str, offset: usize) -> Result<(Vec<T>, usize), GrammarError>
+    where C: Default {
+        if !code.is_char_boundary(offset) {
+            return Err(GrammarError::InvalidOffset { offset });
         }
 
-        Ok(())
+        self.scanner(root_id, code, Some(&mut C::default()))?.scan_prefix(root_id, offset)
     }
-}
 
-fn add_extra<T, F>(target: &Rule<T>, not: bool, min: u64, max: u64, f: F) 
-where F: FnOnce(&Rule<T>) -> &Rule<T> {
-    if min == 1 && max == 1 {
-        if not {
-            let rule = Rule::default();
-            target.not(f(&rule));
+    /// Scans the records of `reader` one after the other with rule `root_id`, every item is the values of a record.
+    /// The reader is read as far as a record needs, so a record may be split over reads. Error positions are positions
+    /// in all of the input.
+    pub fn scan_reader<'a, R: BufRead>(&'a self, root_id: &'a str, reader: R) -> ScanReader<'a, T, C, R>
+    where C: Default {
+        ScanReader::new(self, root_id, reader, C::default())
+    }
+
+    /// The first part of `haystack` that rule `root_id` matches, searching from the start of the haystack. Unlike
+    /// `scan` the match does not have to reach the end.
+    pub fn find<'a>(&'a self, root_id: &'a str, haystack: &'a str) -> Result<Option<Match<'a, T>>, GrammarError>
+    where C: Default {
+        self.find_iter(root_id, haystack).next().transpose()
+    }
+
+    /// All parts of `haystack` that rule `root_id` matches that do not overlap, from the start of the haystack.
+    pub fn find_iter<'a>(&'a self, root_id: &'a str, haystack: &'a str) -> FindIter<'a, T, C>
+    where C: Default {
+        FindIter::new(self, root_id, haystack, C::default())
+    }
+
+    /// Replaces every match of `find_iter` in `haystack` with what `replace_fn` returns for it.
+    pub fn replace_all<F>(&self, root_id: &str, haystack: &str, mut replace_fn: F) -> Result<String, GrammarError>
+    where C: Default, F: FnMut(&Match<T>) -> String {
+        let mut replaced = String::with_capacity(haystack.len());
+        let mut offset = 0;
+
+        for m in self.find_iter(root_id, haystack) {
+            let m = m?;
+            replaced.push_str(&haystack[offset..m.span.start]);
+            replaced.push_str(&replace_fn(&m));
+            offset = m.span.end;
         }
-        else {
-            f(target);
+
+        replaced.push_str(&haystack[offset..]);
+        Ok(replaced)
+    }
+
+    /// Whether rule `root_id` matches a part of `haystack`.
+    pub fn is_match(&self, root_id: &str, haystack: &str) -> Result<bool, GrammarError>
+    where C: Default {
+        self.find(root_id, haystack).map(|x| x.is_some())
+    }
+
+    /// Like `scan`, but also returns the counters of the scan.
+    pub fn scan_with_stats(&self, root_id: &str, code: &str) -> (Result<Vec<T>, GrammarError>, ScanStats)
+    where C: Default {
+        match self.scanner(root_id, code, Some(&mut C::default())) {
+            Ok(scanner) => scanner.scan_with_stats(root_id),
+            Err(err) => (Err(err), ScanStats::default()),
         }
     }
-    else {
-        if not {
-            let rule = Rule::default();
-            let quantity = Rule::default();
-            quantity.between(min, max, f(&rule));
-            target.not(&quantity);
+
+    fn match_at(&self, root_id: &str, haystack: &str, start: usize, ctx: &mut C) -> Result<Option<(Vec<T>, usize)>, GrammarError> {
+        self.scanner(root_id, haystack, Some(ctx))?.match_at(root_id, start)
+    }
+
+    fn scanner<'a>(&'a self, root_id: &str, code: &'a str, ctx: Option<&'a mut C>) -> Result<Scanner<'a, T, C>, GrammarError> {
+        if self.rules.contains_key(root_id) {
+            Ok(Scanner::new(&self.rules, &self.ws, code, ctx).memoize(self.memo_clone, &self.context_rules))
         }
         else {
-            let rule = Rule::default();
-            target.between(min, max, f(&rule));
-        }
+            Err(GrammarError::UnknownRoot { id: String::from(root_id) })
+        }   
     }
 }
 
-impl fmt::Display for GrammarError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.msg)
+impl<T: Clone, C> CompiledGrammar<T, C> {
+    /// Turns memoization on or off. When on, the result of every rule at every position is kept during a scan, so a
+    /// rule that is tried again at the same position after backtracking is not scanned again. A remembered result
+    /// keeps the values its rule produced and refers to those of the rules it called, it is replayed by cloning them
+    /// and the branch functions are not called again. The nodes of `CompiledGrammar::parse_tree` are still copied into
+    /// every remembered rule that contains them. Rules that can reach a `Grammar::map_with_context` rule are never remembered, so the context
+    /// ends up the same with or without memoization.
+    pub fn memoize(&mut self, enabled: bool) {
+        self.memo_clone = if enabled { Some(T::clone) } else { None };
     }
 }
 
-impl Error for GrammarError {
-    fn description(&self) -> &str {
-        "Grammer error"
+impl<T, C> Default for Grammar<T, C> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-impl From<String> for GrammarError {
-    fn from(err_msg: String) -> Self {
-        GrammarError {
-            msg: err_msg,
+// An expression that starts with `(?x)` is verbose: its layout and `#` comments are dropped before it is parsed, so
+// whitespace has to be written as `_` or `_*` and a space or `#` as `\ ` or `\#`. The `@...@` messages, sets, alters
+// and ids are kept as is, so `[a #]` is a set of three chars.
+fn parse(parser: &GrammarRules<ParseData>, expr: &str) -> Result<Vec<Clause>, GrammarError> {
+    match expr.strip_prefix(VERBOSE) {
+        Some(verbose) => {
+            let (stripped, offsets) = strip_layout(verbose);
+            parse_(parser, &stripped)
+                .map_err(|err| err.map_position(|x| Position::locate(expr, VERBOSE.len() + offsets[x.offset])))
+        },
+        None => parse_(parser, expr),
+    }
+}
+
+fn parse_(parser: &GrammarRules<ParseData>, expr: &str) -> Result<Vec<Clause>, GrammarError> {
+    Scanner::new(parser, &[], expr, None).scan("root")
+        .map(|parse_data| parse_data.into_iter().map(|x| x.unwrap_clause()).collect())
+}
+
+// Drops the unescaped whitespace and comments of a verbose expression. Also returns for every byte of the result, and
+// for its end, the offset it had in `expr`.
+fn strip_layout(expr: &str) -> (String, Vec<usize>) {
+    let mut stripped = String::with_capacity(expr.len());
+    let mut offsets = Vec::with_capacity(expr.len() + 1);
+    let mut chars = expr.char_indices();
+
+    // The char that ends the `@...@` message, `[...]` set, `(~...)` alter or `<...>` id the expression is in. These
+    // have no layout, only outside of them whitespace and comments are dropped and an `@` starts a message.
+    let mut closing = None;
+    let mut after_paren = false;
+
+    let mut keep = |stripped: &mut String, i: usize, c: char| {
+        offsets.extend(i..i + c.len_utf8());
+        stripped.push(c);
+    };
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                keep(&mut stripped, i, c);
+
+                if let Some((j, escaped)) = chars.next() {
+                    keep(&mut stripped, j, escaped);
+                }
+            },
+            _ if closing.is_some() => {
+                keep(&mut stripped, i, c);
+
+                if closing == Some(c) {
+                    closing = None;
+                }
+            },
+            '#' => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+
+                continue;
+            },
+            ' ' | '\t' | '\n' | '\r' => continue,
+            _ => {
+                keep(&mut stripped, i, c);
+
+                closing = match c {
+                    '@' => Some('@'),
+                    '[' => Some(']'),
+                    '<' => Some('>'),
+                    '~' if after_paren => Some(')'),
+                    _ => None,
+                };
+            },
         }
+
+        after_paren = c == '(' && closing.is_none();
     }
+
+    offsets.push(expr.len());
+    (stripped, offsets)
 }
 
-impl From<RuleError> for GrammarError {
-    fn from(err: RuleError) -> Self {
-        GrammarError { 
-            msg: format!("{}", err),
+fn unknown_rules<T, C>(id: Option<&String>, sentence: &[Clause], all_rules: &GrammarRules<T, C>, unknown: &mut Vec<UnknownRule>) {
+    for (i, clause) in sentence.iter().enumerate() {
+        let mut ids = vec![];
+        clause_ids(clause, &mut ids);
+
+        for name in ids {
+            if !all_rules.contains_key(name) {
+                unknown.push(UnknownRule { id: name.clone(), rule: id.cloned(), clause: i });
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+// Collects the ids of the rules a clause refers to, including those inside groups.
+fn clause_ids<'a>(clause: &'a Clause, ids: &mut Vec<&'a String>) {
+    match clause {
+        Clause::AnyOf { ref sentences, .. } => {
+            for x in sentences.iter().flatten() {
+                clause_ids(x, ids);
+            }
+        },
+        Clause::Id { ref name, .. } => {
+            ids.push(name);
+        },
+        _ => {},
+    }
+}
@@ -0,0 +1,113 @@
+// Copyright (c) 2015-2023 Vincent van Ingen <code@abitvin.com>
+// Licensed under the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use super::ast::Clause;
+use std::sync::Arc;
+
+type UnaryFn<T> = dyn Fn(T, &str) -> Result<T, String> + Send + Sync;
+type BinaryFn<T> = dyn Fn(T, T, &str) -> Result<T, String> + Send + Sync;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Assoc {
+    Left,
+    Right,
+    None,
+}
+
+pub enum Fixity<T> {
+    Prefix(Arc<UnaryFn<T>>),
+    Postfix(Arc<UnaryFn<T>>),
+    Infix(Assoc, Arc<BinaryFn<T>>),
+}
+
+pub struct Operator<T> {
+    pub expr: String,
+    pub sentence: Vec<Clause>,
+    pub precedence: u32,
+    pub fixity: Fixity<T>,
+}
+
+pub struct OperatorTable<T> {
+    pub operand: String,
+    pub ops: Vec<Operator<T>>,
+}
+
+/// The operators of a `Grammar::operators` rule. Every operator is written in the same expression language as the
+/// rules, e.g. `" \\+ "` or `" mod "`, and operators that bind tighter get a higher precedence. When more operators
+/// match at the same position the first one added wins, so add `**` before `*`. Whatever the operator expressions
+/// produce themselves is discarded, the combine functions get the operands and the text of the operator. An operator
+/// has to consume input, `Grammar::compile` rejects one that can match empty input.
+pub struct Operators<T> {
+    ops: Vec<(String, u32, Fixity<T>)>,
+}
+
+impl<T> Default for Operators<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Operators<T> {
+    pub fn new() -> Self {
+        Self {
+            ops: vec![],
+        }
+    }
+
+    pub fn infix(mut self, expr: &str, precedence: u32, assoc: Assoc, combine_fn: impl Fn(T, T, &str) -> Result<T, String> + Send + Sync + 'static) -> Self {
+        self.ops.push((String::from(expr), precedence, Fixity::Infix(assoc, Arc::new(combine_fn))));
+        self
+    }
+
+    pub fn prefix(mut self, expr: &str, precedence: u32, combine_fn: impl Fn(T, &str) -> Result<T, String> + Send + Sync + 'static) -> Self {
+        self.ops.push((String::from(expr), precedence, Fixity::Prefix(Arc::new(combine_fn))));
+        self
+    }
+
+    pub fn postfix(mut self, expr: &str, precedence: u32, combine_fn: impl Fn(T, &str) -> Result<T, String> + Send + Sync + 'static) -> Self {
+        self.ops.push((String::from(expr), precedence, Fixity::Postfix(Arc::new(combine_fn))));
+        self
+    }
+
+    pub(crate) fn into_table<F, E>(self, operand: &str, mut parse: F) -> Result<OperatorTable<T>, E>
+    where F: FnMut(&str) -> Result<Vec<Clause>, E> {
+        let mut ops = vec![];
+
+        for (expr, precedence, fixity) in self.ops {
+            ops.push(Operator { sentence: parse(&expr)?, expr, precedence, fixity });
+        }
+
+        Ok(OperatorTable { operand: String::from(operand), ops })
+    }
+}
+
+impl<T> OperatorTable<T> {
+    // A stand-in for the rule as an expression, `(prefix)* <operand> (infix|postfix)*`, so the rules used by the
+    // operators are checked at compile time like those of any other rule.
+    pub fn sentence(&self) -> Vec<Clause> {
+        let mut prefix = vec![];
+        let mut others = vec![];
+
+        for op in &self.ops {
+            match op.fixity {
+                Fixity::Prefix(_) => prefix.push(op.sentence.clone()),
+                _ => others.push(op.sentence.clone()),
+            }
+        }
+
+        let mut sentence = vec![];
+
+        if !prefix.is_empty() {
+            sentence.push(Clause::AnyOf { lookahead: None, sentences: prefix, min: 0, max: u64::MAX });
+        }
+
+        sentence.push(Clause::Id { lookahead: None, name: self.operand.clone(), capture: None, min: 1, max: 1 });
+
+        if !others.is_empty() {
+            sentence.push(Clause::AnyOf { lookahead: None, sentences: others, min: 0, max: u64::MAX });
+        }
+
+        sentence
+    }
+}
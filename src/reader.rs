@@ -0,0 +1,125 @@
+// Copyright (c) 2015-2023 Vincent van Ingen <code@abitvin.com>
+// Licensed under the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use super::error::{Expected, GrammarError, Position};
+use super::CompiledGrammar;
+use std::io::{self, BufRead};
+use std::str;
+use std::sync::Arc;
+
+/// The records of a reader, see `CompiledGrammar::scan_reader`. Every item is the values of one record. The iterator
+/// ends at the end of the input or after the first error.
+pub struct ScanReader<'a, T, C, R> {
+    grammar: &'a CompiledGrammar<T, C>,
+    root_id: &'a str,
+    reader: R,
+    ctx: C,
+    buffer: String,
+    partial: Vec<u8>,
+    base: Position,
+    eof: bool,
+    done: bool,
+}
+
+impl<'a, T, C, R: BufRead> ScanReader<'a, T, C, R> {
+    pub(crate) fn new(grammar: &'a CompiledGrammar<T, C>, root_id: &'a str, reader: R, ctx: C) -> Self {
+        Self {
+            grammar,
+            root_id,
+            reader,
+            ctx,
+            buffer: String::new(),
+            partial: vec![],
+            base: Position { offset: 0, line: 1, column: 1 },
+            eof: false,
+            done: false,
+        }
+    }
+
+    // Appends the next read to the buffer. A char split by the read is kept apart until the rest of it is read.
+    fn fill(&mut self) -> io::Result<()> {
+        let len = loop {
+            match self.reader.fill_buf() {
+                Ok(bytes) => {
+                    self.partial.extend_from_slice(bytes);
+                    break bytes.len();
+                },
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        };
+
+        self.reader.consume(len);
+        self.eof = len == 0;
+
+        let valid = match str::from_utf8(&self.partial) {
+            Ok(_) => self.partial.len(),
+            Err(err) if err.error_len().is_none() && !self.eof => err.valid_up_to(),
+            Err(_) => return Err(io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8")),
+        };
+
+        self.buffer.push_str(str::from_utf8(&self.partial[..valid]).unwrap());
+        self.partial.drain(..valid);
+        Ok(())
+    }
+
+    fn fail(&mut self, err: GrammarError) -> Option<Result<Vec<T>, GrammarError>> {
+        self.done = true;
+        Some(Err(err))
+    }
+}
+
+impl<'a, T, C, R: BufRead> Iterator for ScanReader<'a, T, C, R> {
+    type Item = Result<Vec<T>, GrammarError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if self.buffer.is_empty() && self.eof {
+                self.done = true;
+                break;
+            }
+
+            if self.buffer.is_empty() {
+                if let Err(err) = self.fill() {
+                    return self.fail(GrammarError::Io { source: Arc::new(err) });
+                }
+
+                continue;
+            }
+
+            let (result, hit_end) = match self.grammar.scanner(self.root_id, &self.buffer, Some(&mut self.ctx)) {
+                Ok(scanner) => scanner.scan_partial(self.root_id, 0),
+                Err(err) => return self.fail(err),
+            };
+
+            // The record may go on, or only match, with more input.
+            if hit_end && !self.eof {
+                if let Err(err) = self.fill() {
+                    return self.fail(GrammarError::Io { source: Arc::new(err) });
+                }
+
+                continue;
+            }
+
+            return match result {
+                // A record that is empty would be found again and again.
+                Ok((_, 0)) => {
+                    let err = GrammarError::parse(self.base, Some(String::from(self.root_id)), vec![Expected::Rule(String::from(self.root_id))]);
+                    self.fail(err)
+                },
+                Ok((values, consumed)) => {
+                    self.base = Position::locate(&self.buffer, consumed).after(self.base);
+                    self.buffer.drain(..consumed);
+                    Some(Ok(values))
+                },
+                Err(err) => {
+                    let base = self.base;
+                    self.fail(err.map_position(|x| x.after(base)))
+                },
+            };
+        }
+
+        None
+    }
+}
@@ -2,134 +2,134 @@
 // Licensed under the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use super::ast::{Clause, ParseData};
-use crate::rule::Rule;
-
-const ESC_CTRL_CHARS: [(&'static str, &'static str); 22] = [
-    ("\\<", "<"), 
-    ("\\>", ">"), 
+use super::ast::{AlterText, CharClass, CharRange, Clause, Lookahead, ParseData};
+use super::{BranchFn, GrammarRule, GrammarRules};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const ESC_CTRL_CHARS: [(&str, &str); 24] = [
+    ("\\<", "<"),
+    ("\\>", ">"),
     ("\\{", "{"),
-    ("\\}", "}"), 
-    ("\\(", "("), 
-    ("\\)", ")"), 
-    ("\\[", "["), 
-    ("\\]", "]"), 
+    ("\\}", "}"),
+    ("\\(", "("),
+    ("\\)", ")"),
+    ("\\[", "["),
+    ("\\]", "]"),
     ("\\^", "^"),
     ("\\~", "~"),
     ("\\-", "-"),
     ("\\,", ","),
     ("\\|", "|"),
-    ("\\+", "+"), 
-    ("\\?", "?"), 
-    ("\\*", "*"), 
-    ("\\.", "."), 
+    ("\\+", "+"),
+    ("\\?", "?"),
+    ("\\*", "*"),
+    ("\\.", "."),
     ("\\$", "$"),
-    ("\\ ", " "), 
+    ("\\ ", " "),
     ("\\_", "_"),
     ("\\!", "!"),
     ("\\@", "@"),
+    ("\\&", "&"),
+    ("\\#", "#"),
+];
+
+// Escapes of chars that are hard to write in an expression.
+const ESC_CHARS: [(&str, &str); 5] = [
+    ("\\n", "\n"),
+    ("\\r", "\r"),
+    ("\\t", "\t"),
+    ("\\0", "\0"),
+    ("\\\\", "\\"),
 ];
 
-pub fn root() -> Rule<ParseData> {
+type Rules = GrammarRules<ParseData>;
+
+// The expression language is scanned by the same machinery as the grammars written in it, so the rules below are
+// written out as clauses by hand.
+pub fn root() -> Rules {
     let f = |mut b: Vec<ParseData>, _: &str| {
         match b.len() {
             1 => {
-                Ok(ParseData::Clause(Clause::from((false, b.remove(0), ParseData::Range { min: 1, max: 1 }))))
+                Ok(ParseData::Clause(Clause::from((None, b.remove(0), ParseData::Range { min: 1, max: 1 }))))
             },
             2 => {
-                if b[0].is_not() {
-                    b.remove(0);
-                    Ok(ParseData::Clause(Clause::from((true, b.remove(0), ParseData::Range { min: 1, max: 1 }))))
+                if b[0].is_lookahead() {
+                    let lookahead = b.remove(0).unwrap_lookahead();
+                    Ok(ParseData::Clause(Clause::from((Some(lookahead), b.remove(0), ParseData::Range { min: 1, max: 1 }))))
                 }
                 else {
-                    Ok(ParseData::Clause(Clause::from((false, b.remove(0), b.remove(0)))))
+                    Ok(ParseData::Clause(Clause::from((None, b.remove(0), b.remove(0)))))
                 }
             },
             3 => {
-                b.remove(0);
-                Ok(ParseData::Clause(Clause::from((true, b.remove(0), b.remove(0)))))
+                let lookahead = b.remove(0).unwrap_lookahead();
+                Ok(ParseData::Clause(Clause::from((Some(lookahead), b.remove(0), b.remove(0)))))
             },
             _ => unreachable!("Unexpected length")
         }
     };
-    
-    let escaped_ctrl_chars = escaped_ctrl_chars();
-    let clause = Rule::new(f);
-    let ranges = ranges();
-    let not = not();
-    
-    let alter_clause = alter(&escaped_ctrl_chars);
-
-    let any_char_clause = Rule::default();
-    any_char_clause.maybe(&not).one(&any_char()).maybe(&ranges);
-    
-    let any_char_except_clause = Rule::default();
-    any_char_except_clause.maybe(&not).one(&any_char_except(&escaped_ctrl_chars)).maybe(&ranges);
-
-    let any_of_clause = Rule::default();
-    any_of_clause.maybe(&not).one(&any_of(&clause)).maybe(&ranges);
-
-    let char_ranges_clause = Rule::default();
-    char_ranges_clause.maybe(&not).one(&char_ranges(&escaped_ctrl_chars)).maybe(&ranges);
-
-    let eof_clause = eof();
-
-    let id_clause = Rule::default();
-    id_clause.maybe(&not).one(&id(&escaped_ctrl_chars)).maybe(&ranges);
-    
-    let literal_clause = Rule::default();
-    literal_clause.maybe(&not).one(&literal(&escaped_ctrl_chars)).maybe(&ranges);
-
-    let at_least_one_ws_clause = at_least_one_ws();
-    let none_or_many_ws_clause = none_or_many_ws();
-
-    let no_backtrack = no_backtrack(&escaped_ctrl_chars);
-    
-    clause.any_of(vec![
-        &any_char_clause, &at_least_one_ws_clause, &none_or_many_ws_clause, 
-        &eof_clause, &alter_clause, &any_char_except_clause, 
-        &char_ranges_clause, &id_clause, &any_of_clause, &no_backtrack,
-        &literal_clause,
-    ]);
 
-    let root = Rule::default();
-    root.none_or_many(&clause);
-    root
+    let mut rules = HashMap::new();
+
+    char_escape(&mut rules);
+    ranges(&mut rules);
+    lookahead(&mut rules);
+    alter(&mut rules);
+    any_char(&mut rules);
+    any_char_except(&mut rules);
+    any_of(&mut rules);
+    char_class(&mut rules);
+    char_ranges(&mut rules);
+    eof(&mut rules);
+    id(&mut rules);
+    ignore_case(&mut rules);
+    literal(&mut rules);
+    at_least_one_ws(&mut rules);
+    none_or_many_ws(&mut rules);
+    no_backtrack(&mut rules);
+
+    for kind in &["any-char", "any-char-except", "any-of", "char-class", "char-ranges", "id", "ignore-case", "literal"] {
+        rule(&mut rules, &format!("{}-clause", kind), vec![maybe("lookahead"), one(kind), maybe("ranges")]);
+    }
+
+    map(&mut rules, "clause", f, vec![any_of_ids(&[
+        "any-char-clause", "none-or-many-ws", "at-least-one-ws",
+        "eof", "alter", "any-char-except-clause",
+        "char-class-clause", "char-ranges-clause", "id-clause", "ignore-case-clause", "any-of-clause", "no-backtrack",
+        "literal-clause",
+    ])]);
+
+    rule(&mut rules, "root", vec![between("clause", 0, u64::MAX)]);
+    rules
 }
 
 // Clauses
 
-pub fn any_char() -> Rule<ParseData> {
+fn any_char(rules: &mut Rules) {
     let f = |_: Vec<ParseData>, _: &str| {
         Ok(ParseData::AnyChar)
     };
 
-    let rule = Rule::new(f);
-    rule.literal(".");
-    rule
+    map(rules, "any-char", f, vec![literal_text(".")]);
 }
 
-pub fn any_char_except(escaped_ctrl_chars: &Rule<ParseData>) -> Rule<ParseData> {
-    let f = |_: Vec<ParseData>, l: &str| {
-        let chars = String::from(l).chars().map(|c| c).collect();
+fn any_char_except(rules: &mut Rules) {
+    let f = |b: Vec<ParseData>, _: &str| {
+        let chars = b.into_iter().map(|x| x.unwrap_char()).collect();
         Ok(ParseData::AnyCharExcept(chars))
     };
 
-    let any_other = Rule::default(); 
-    any_other.any_char_except(vec![']']);
-
-    let chr = Rule::default(); 
-    chr.any_of(vec![escaped_ctrl_chars, &any_other]);
+    char_rule(rules, "any-char-except-char", vec![']']);
 
-    let chars = Rule::new(f); 
-    chars.at_least(1, &chr);
-
-    let rule = Rule::default(); 
-    rule.literal("[^").one(&chars).literal("]");
-    rule
+    // A `\p{..}` is not a char to except, so `[^\p{L}]` is a syntax error instead of excepting `\`, `p`, `{`, `L` and `}`.
+    let not_class = Clause::Id { lookahead: Some(Lookahead::Not), name: String::from("char-class"), capture: None, min: 1, max: 1 };
+    rule(rules, "any-char-except-part", vec![not_class, one("any-char-except-char")]);
+    map(rules, "any-char-except-chars", f, vec![between("any-char-except-part", 1, u64::MAX)]);
+    rule(rules, "any-char-except", vec![literal_text("[^"), one("any-char-except-chars"), literal_text("]")]);
 }
 
-pub fn any_of(clause: &Rule<ParseData>) -> Rule<ParseData> {
+fn any_of(rules: &mut Rules) {
     let any_of_fn = |b: Vec<ParseData>, _: &str| {
         let unwrapped = b
             .into_iter()
@@ -148,18 +148,13 @@ pub fn any_of(clause: &Rule<ParseData>) -> Rule<ParseData> {
         Ok(ParseData::Clauses(unwrapped))
     };
 
-    let sentence = Rule::new(sentence_fn);
-    sentence.at_least(1, clause);
-
-    let more = Rule::default();
-    more.literal("|").one(&sentence);
+    let more = Clause::AnyOf { lookahead: None, sentences: vec![vec![literal_text("|"), one("sentence")]], min: 0, max: u64::MAX };
 
-    let rule = Rule::new(any_of_fn);
-    rule.literal("(").one(&sentence).none_or_many(&more).literal(")");
-    rule
+    map(rules, "sentence", sentence_fn, vec![between("clause", 1, u64::MAX)]);
+    map(rules, "any-of", any_of_fn, vec![literal_text("("), one("sentence"), more, literal_text(")")]);
 }
 
-pub fn alter(escaped_ctrl_chars: &Rule<ParseData>) -> Rule<ParseData> {
+fn alter(rules: &mut Rules) {
     let f = |b: Vec<ParseData>, _: &str| {
         let to_alter = b
             .into_iter()
@@ -169,292 +164,356 @@ pub fn alter(escaped_ctrl_chars: &Rule<ParseData>) -> Rule<ParseData> {
         Ok(ParseData::AlterTexts(to_alter))
     };
 
-    let alter_tuple = alter_tuple(escaped_ctrl_chars);
+    let more = Clause::AnyOf { lookahead: None, sentences: vec![vec![literal_text("|"), one("alter-tuple")]], min: 0, max: u64::MAX };
 
-    let more = Rule::default();
-    more.literal("|").one(&alter_tuple);
-
-    let alter = Rule::new(f);
-    alter.literal("(~").one(&alter_tuple).none_or_many(&more).literal(")");
-    alter
+    alter_tuple(rules);
+    map(rules, "alter", f, vec![literal_text("(~"), one("alter-tuple"), more, literal_text(")")]);
 }
 
-pub fn alter_tuple(escaped_ctrl_chars: &Rule<ParseData>) -> Rule<ParseData> {
+fn alter_tuple(rules: &mut Rules) {
     let tuple_fn = |mut b: Vec<ParseData>, _: &str| {
         let replace = b.pop().unwrap().unwrap_text();
         let find = b.pop().unwrap().unwrap_text();
         Ok(ParseData::AlterText{ find, replace })
     };
 
-    let left_text_fn = |_: Vec<ParseData>, l: &str| {
-        Ok(ParseData::Text(String::from(l)))
+    let left_text_fn = |b: Vec<ParseData>, _: &str| {
+        Ok(ParseData::Text(text(b)))
     };
 
-    let right_text_fn = |_: Vec<ParseData>, l: &str| {
-        Ok(ParseData::Text(String::from(l)))
+    let right_text_fn = |b: Vec<ParseData>, _: &str| {
+        Ok(ParseData::Text(text(b)))
     };
 
-    let all_except_left_char = Rule::default();
-    all_except_left_char.any_char_except(vec![',']);
-    
-    let left_char = Rule::default();
-    left_char.any_of(vec![escaped_ctrl_chars, &all_except_left_char]);
-
-    let left_text = Rule::new(left_text_fn);
-    left_text.at_least(1, &left_char);
-
-    let all_except_right_char = Rule::default();
-    all_except_right_char.any_char_except(vec!['|', ')']);
-    
-    let right_char = Rule::default();
-    right_char.any_of(vec![escaped_ctrl_chars, &all_except_right_char]);
-
-    let right_text = Rule::new(right_text_fn);
-    right_text.at_least(1, &right_char);
-
-    let tuple = Rule::new(tuple_fn);
-    tuple.one(&left_text).literal(",").one(&right_text);
-    tuple
+    char_rule(rules, "alter-left-char", vec![',']);
+    char_rule(rules, "alter-right-char", vec!['|', ')']);
+    map(rules, "alter-left-text", left_text_fn, vec![between("alter-left-char", 1, u64::MAX)]);
+    map(rules, "alter-right-text", right_text_fn, vec![between("alter-right-char", 1, u64::MAX)]);
+    map(rules, "alter-tuple", tuple_fn, vec![one("alter-left-text"), literal_text(","), one("alter-right-text")]);
 }
 
-pub fn char_ranges(escaped_ctrl_chars: &Rule<ParseData>) -> Rule<ParseData> {
-    let char_range_fn = |_: Vec<ParseData>, l: &str| {
-        let mut chars = l.chars();
-        let start = chars.next().unwrap();
-        let end = chars.skip(1).next().unwrap();
+fn char_ranges(rules: &mut Rules) {
+    let char_range_fn = |mut b: Vec<ParseData>, _: &str| {
+        let end = b.pop().unwrap().unwrap_char();
+        let start = b.pop().unwrap().unwrap_char();
         Ok(ParseData::CharRange { start, end })
     };
 
-    let char_ranges_fn = |b: Vec<ParseData>, _: &str| {
-        let char_ranges = b
-            .into_iter()
-            .map(|x| x.unwrap_char_range())
-            .collect();
-
-        Ok(ParseData::CharRanges(char_ranges))
+    let single_char_fn = |mut b: Vec<ParseData>, _: &str| {
+        let c = b.pop().unwrap().unwrap_char();
+        Ok(ParseData::CharRange { start: c, end: c })
     };
 
-    let char_range_char = char_range_char(escaped_ctrl_chars);
+    let char_ranges_fn = |b: Vec<ParseData>, _: &str| {
+        let mut ranges = vec![];
+        let mut classes = vec![];
+
+        for x in b {
+            match x {
+                ParseData::CharClass(class) => classes.push(class),
+                range => ranges.push(range.unwrap_char_range()),
+            }
+        }
 
-    let char_range = Rule::new(char_range_fn);
-    char_range.one(&char_range_char).literal("-").one(&char_range_char);
+        Ok(ParseData::CharRanges { ranges, classes })
+    };
 
-    let char_ranges = Rule::new(char_ranges_fn);
-    char_ranges.literal("[").at_least(1, &char_range).literal("]");
+    char_rule(rules, "char-range-char", vec!['-', ']']);
+    map(rules, "char-range", char_range_fn, vec![one("char-range-char"), literal_text("-"), one("char-range-char")]);
+    map(rules, "char-range-single", single_char_fn, vec![one("char-range-char")]);
+    rule(rules, "char-ranges-item", vec![any_of_ids(&["char-class", "char-range", "char-range-single"])]);
 
-    char_ranges
+    // A set starting with `^` is always one of chars to except, so a `[^..]` that is not valid is not taken as a set
+    // with a `^` instead.
+    let not_caret = Clause::Literal { lookahead: Some(Lookahead::Not), text: String::from("^"), ignore_case: false, min: 1, max: 1 };
+    map(rules, "char-ranges", char_ranges_fn, vec![literal_text("["), not_caret, between("char-ranges-item", 1, u64::MAX), literal_text("]")]);
 }
 
-pub fn char_range_char(escaped_ctrl_chars: &Rule<ParseData>) -> Rule<ParseData> {
-    let all_except = Rule::default();
-    all_except.any_char_except(vec!['-', ']']);
+// A Unicode property, `\p{L}` or `\p{Greek}`, or all chars without it, `\P{L}`.
+fn char_class(rules: &mut Rules) {
+    let f = |_: Vec<ParseData>, l: &str| {
+        let name = &l[3..l.len() - 1];
+        CharClass::new(name, l.starts_with("\\P")).map(ParseData::CharClass)
+    };
 
-    let rule = Rule::default();
-    rule.any_of(vec![escaped_ctrl_chars, &all_except]);
-    rule
+    let name = Clause::AnyCharExcept { lookahead: None, chars: vec!['}'], folded: None, min: 1, max: u64::MAX };
+    let start = Clause::AnyOf { lookahead: None, sentences: vec![vec![literal_text("\\p{")], vec![literal_text("\\P{")]], min: 1, max: 1 };
+    map(rules, "char-class", f, vec![start, name, literal_text("}")]);
 }
 
-pub fn eof() -> Rule<ParseData> {
+fn eof(rules: &mut Rules) {
     let f = |_: Vec<ParseData>, _: &str| {
         Ok(ParseData::Eof)
     };
 
-    let rule = Rule::new(f);
-    rule.literal("$");
-    rule
+    map(rules, "eof", f, vec![literal_text("$")]);
 }
 
-pub fn escaped_ctrl_chars() -> Rule<ParseData> {
-    let rule = Rule::default();
-    rule.alter(ESC_CTRL_CHARS.to_vec());
-    rule
-}
+fn id(rules: &mut Rules) {
+    let f = |b: Vec<ParseData>, _: &str| {
+        let text = text(b);
 
-pub fn id(escaped_ctrl_chars: &Rule<ParseData>) -> Rule<ParseData> {
-    let f = |_: Vec<ParseData>, l: &str| {
-        Ok(ParseData::Id(String::from(l)))
+        match text.split_once(':') {
+            Some((capture, name)) => Ok(ParseData::Id { name: String::from(name), capture: Some(String::from(capture)) }),
+            None => Ok(ParseData::Id { name: text, capture: None }),
+        }
     };
 
-    let any_char_except = Rule::default();
-    any_char_except.any_char_except(vec!['>']);
+    char_rule(rules, "id-char", vec!['>']);
+    map(rules, "id-text", f, vec![between("id-char", 1, u64::MAX)]);
+    rule(rules, "id", vec![literal_text("<"), one("id-text"), literal_text(">")]);
+}
 
-    let chr = Rule::default();
-    chr.any_of(vec![escaped_ctrl_chars, &any_char_except]);
+// A group like `(?i:select|insert)` that ignores the case of its literals, char ranges and alter clauses.
+fn ignore_case(rules: &mut Rules) {
+    let f = |b: Vec<ParseData>, _: &str| {
+        let mut sentences: Vec<Vec<Clause>> = b
+            .into_iter()
+            .map(|x| x.unwrap_clauses())
+            .collect();
 
-    let id = Rule::new(f);
-    id.at_least(1, &chr);
+        sentences.iter_mut().flatten().for_each(Clause::ignore_case);
+        Ok(ParseData::AnyOf(sentences))
+    };
 
-    let rule = Rule::default();
-    rule.literal("<").one(&id).literal(">");
-    rule
+    let more = Clause::AnyOf { lookahead: None, sentences: vec![vec![literal_text("|"), one("sentence")]], min: 0, max: u64::MAX };
+    map(rules, "ignore-case", f, vec![literal_text("(?i:"), one("sentence"), more, literal_text(")")]);
 }
 
-pub fn integer() -> Rule<ParseData> {
+fn integer(rules: &mut Rules) {
     let f = |_: Vec<ParseData>, l: &str| {
-        Ok(ParseData::Integer(l.parse::<u64>().unwrap()))
+        l.parse::<u64>()
+            .map(ParseData::Integer)
+            .map_err(|e| format!("Invalid integer \"{}\": {}", l, e))
     };
 
-    let digit = Rule::default();
-    digit.char_in('0', '9');
-
-    let integer = Rule::new(f);
-    integer.at_least(1, &digit);
-    integer
+    let digit = Clause::CharRanges { lookahead: None, ranges: vec![CharRange { start: '0', end: '9' }], classes: vec![], folded: None, min: 1, max: u64::MAX };
+    map(rules, "integer", f, vec![digit]);
 }
 
-pub fn literal(escaped_ctrl_chars: &Rule<ParseData>) -> Rule<ParseData> {
-    let f = |_: Vec<ParseData>, l: &str| {
-        Ok(ParseData::Literal(String::from(l)))
+fn literal(rules: &mut Rules) {
+    let f = |b: Vec<ParseData>, _: &str| {
+        Ok(ParseData::Literal(text(b)))
     };
 
-    let all_except = Rule::default();
-    all_except.any_char_except(vec!['<', '{', '(', ')', '|', '[', '+', '?', '*', '.', '$', ' ', '_', '!']);
+    let all_except = vec!['<', '{', '(', ')', '|', '[', '+', '?', '*', '.', '$', ' ', '_', '!', '&'];
+    char_rule(rules, "literal-char", all_except);
 
-    let chr = Rule::default();
-    chr.any_of(vec![escaped_ctrl_chars, &all_except]);
-    
-    let rule = Rule::new(f);
-    rule.at_least(1, &chr);
-    rule
+    // A `\p{..}` ends the literal, it is a clause of its own.
+    let not_class = Clause::Id { lookahead: Some(Lookahead::Not), name: String::from("char-class"), capture: None, min: 1, max: 1 };
+    rule(rules, "literal-part", vec![not_class, one("literal-char")]);
+    map(rules, "literal", f, vec![between("literal-part", 1, u64::MAX)]);
 }
 
-pub fn at_least_one_ws() -> Rule<ParseData> {
+fn at_least_one_ws(rules: &mut Rules) {
     let f = |_: Vec<ParseData>, _: &str| {
-        Ok(ParseData::Whitespace { min: 1, max: ::std::u64::MAX })
+        Ok(ParseData::Whitespace { min: 1, max: u64::MAX })
     };
-    
-    let rule = Rule::new(f);
-    rule.literal("_");
-    rule
+
+    map(rules, "at-least-one-ws", f, vec![literal_text("_")]);
 }
 
-pub fn none_or_many_ws() -> Rule<ParseData> {
+fn none_or_many_ws(rules: &mut Rules) {
     let f = |_: Vec<ParseData>, _: &str| {
-        Ok(ParseData::Whitespace { min: 0, max: ::std::u64::MAX })
+        Ok(ParseData::Whitespace { min: 0, max: u64::MAX })
     };
 
-    let rule = Rule::new(f);
-    rule.literal(" ");
-    rule
+    // The `_*` is for verbose expressions, where a space is layout.
+    let sentences = vec![vec![literal_text(" ")], vec![literal_text("_*")]];
+    map(rules, "none-or-many-ws", f, vec![Clause::AnyOf { lookahead: None, sentences, min: 1, max: 1 }]);
 }
 
-// Ranges
+// Escapes
 
-pub fn ranges() -> Rule<ParseData> {
-    let integer = integer();
+// A char written as its value, `\x00` to `\xFF` or `\u{0}` to `\u{10FFFF}`.
+fn char_escape(rules: &mut Rules) {
+    let hex_fn = |_: Vec<ParseData>, l: &str| {
+        let value = u8::from_str_radix(&l[2..], 16).unwrap();
+        Ok(ParseData::Char(char::from(value)))
+    };
+
+    let unicode_fn = |_: Vec<ParseData>, l: &str| {
+        let value = u32::from_str_radix(&l[3..l.len() - 1], 16).unwrap();
+        char::from_u32(value)
+            .map(ParseData::Char)
+            .ok_or_else(|| format!("Invalid char escape \"{}\".", l))
+    };
 
-    let rule = Rule::default();
-    
-    rule.any_of(vec![
-        &at_least(&integer), &at_least_one(), &at_most(&integer), 
-        &between(&integer), &exact(&integer), &maybe(), &none_or_many()
-    ]);
+    map(rules, "hex-escape", hex_fn, vec![literal_text("\\x"), hex_digits(2, 2)]);
+    map(rules, "unicode-escape", unicode_fn, vec![literal_text("\\u{"), hex_digits(1, 6), literal_text("}")]);
+    rule(rules, "char-escape", vec![any_of_ids(&["hex-escape", "unicode-escape"])]);
+}
+
+fn hex_digits(min: u64, max: u64) -> Clause {
+    let ranges = vec![CharRange { start: '0', end: '9' }, CharRange { start: 'a', end: 'f' }, CharRange { start: 'A', end: 'F' }];
+    Clause::CharRanges { lookahead: None, ranges, classes: vec![], folded: None, min, max }
+}
+
+// Ranges
 
-    rule
+fn ranges(rules: &mut Rules) {
+    integer(rules);
+    at_least(rules);
+    at_least_one(rules);
+    at_most(rules);
+    between_range(rules);
+    exact(rules);
+    maybe_range(rules);
+    none_or_many(rules);
+
+    rule(rules, "ranges", vec![any_of_ids(&[
+        "at-least", "at-least-one", "at-most",
+        "between", "exact", "maybe", "none-or-many",
+    ])]);
 }
 
-pub fn at_least(integer: &Rule<ParseData>) -> Rule<ParseData> {
+fn at_least(rules: &mut Rules) {
     let f = |mut b: Vec<ParseData>, _: &str| {
         let count = b.pop().unwrap().unwrap_int();
-        Ok(ParseData::Range { min: count, max: ::std::u64::MAX })
+        Ok(ParseData::Range { min: count, max: u64::MAX })
     };
 
-    let rule = Rule::new(f);
-    rule.literal("{").one(integer).literal(",}");
-    rule
+    map(rules, "at-least", f, vec![literal_text("{"), one("integer"), literal_text(",}")]);
 }
 
-pub fn at_least_one() -> Rule<ParseData> {
+fn at_least_one(rules: &mut Rules) {
     let f = |_: Vec<ParseData>, _: &str| {
-        Ok(ParseData::Range { min: 1, max: ::std::u64::MAX })
+        Ok(ParseData::Range { min: 1, max: u64::MAX })
     };
 
-    let rule = Rule::new(f);
-    rule.literal("+");
-    rule
+    map(rules, "at-least-one", f, vec![literal_text("+")]);
 }
 
-pub fn at_most(integer: &Rule<ParseData>) -> Rule<ParseData> {
+fn at_most(rules: &mut Rules) {
     let f = |mut b: Vec<ParseData>, _: &str| {
         let count = b.pop().unwrap().unwrap_int();
         Ok(ParseData::Range { min: 0, max: count })
     };
 
-    let rule = Rule::new(f);
-    rule.literal("{,").one(integer).literal("}");
-    rule
+    map(rules, "at-most", f, vec![literal_text("{,"), one("integer"), literal_text("}")]);
 }
 
-pub fn between(integer: &Rule<ParseData>) -> Rule<ParseData> {
+fn between_range(rules: &mut Rules) {
     let f = |mut b: Vec<ParseData>, _: &str| {
         let max = b.pop().unwrap().unwrap_int();
         let min = b.pop().unwrap().unwrap_int();
         Ok(ParseData::Range { min, max })
     };
 
-    let rule = Rule::new(f);
-    rule.literal("{").one(integer).literal(",").one(integer).literal("}");
-    rule
+    map(rules, "between", f, vec![literal_text("{"), one("integer"), literal_text(","), one("integer"), literal_text("}")]);
 }
 
-pub fn exact(integer: &Rule<ParseData>) -> Rule<ParseData> {
+fn exact(rules: &mut Rules) {
     let f = |mut b: Vec<ParseData>, _: &str| {
         let count = b.pop().unwrap().unwrap_int();
         Ok(ParseData::Range { min: count, max: count })
     };
 
-    let rule = Rule::new(f);
-    rule.literal("{").one(integer).literal("}");
-    rule
+    map(rules, "exact", f, vec![literal_text("{"), one("integer"), literal_text("}")]);
 }
 
-pub fn maybe() -> Rule<ParseData> {
+fn maybe_range(rules: &mut Rules) {
     let f = |_: Vec<ParseData>, _: &str| {
         Ok(ParseData::Range { min: 0, max: 1 })
     };
 
-    let rule = Rule::new(f);
-    rule.literal("?");
-    rule
+    map(rules, "maybe", f, vec![literal_text("?")]);
 }
 
-pub fn none_or_many() -> Rule<ParseData> {
+fn none_or_many(rules: &mut Rules) {
     let f = |_: Vec<ParseData>, _: &str| {
-        Ok(ParseData::Range { min: 0, max: ::std::u64::MAX })
+        Ok(ParseData::Range { min: 0, max: u64::MAX })
     };
 
-    let rule = Rule::new(f);
-    rule.literal("*");
-    rule
+    map(rules, "none-or-many", f, vec![literal_text("*")]);
 }
 
-pub fn not() -> Rule<ParseData> {
-    let f = |_: Vec<ParseData>, _: &str| {
-        Ok(ParseData::Not)
+fn lookahead(rules: &mut Rules) {
+    let not_fn = |_: Vec<ParseData>, _: &str| {
+        Ok(ParseData::Lookahead(Lookahead::Not))
+    };
+
+    let and_fn = |_: Vec<ParseData>, _: &str| {
+        Ok(ParseData::Lookahead(Lookahead::And))
     };
 
-    let rule = Rule::new(f);
-    rule.literal("!");
-    rule
+    map(rules, "not", not_fn, vec![literal_text("!")]);
+    map(rules, "and", and_fn, vec![literal_text("&")]);
+    rule(rules, "lookahead", vec![any_of_ids(&["not", "and"])]);
 }
 
 // No backtrace
 
-pub fn no_backtrack(escaped_ctrl_chars: &Rule<ParseData>) -> Rule<ParseData> {
-    let f = |_: Vec<ParseData>, l: &str| {
-        Ok(ParseData::NoBacktrack(String::from(l)))
+fn no_backtrack(rules: &mut Rules) {
+    let f = |b: Vec<ParseData>, _: &str| {
+        Ok(ParseData::NoBacktrack(text(b)))
     };
 
-    let any_char_except = Rule::default();
-    any_char_except.any_char_except(vec!['@']);
+    char_rule(rules, "no-backtrack-char", vec!['@']);
+    map(rules, "no-backtrack-msg", f, vec![between("no-backtrack-char", 0, u64::MAX)]);
+    rule(rules, "no-backtrack", vec![literal_text("@"), one("no-backtrack-msg"), literal_text("@")]);
+}
+
+// Building blocks
 
-    let chr = Rule::default();
-    chr.any_of(vec![escaped_ctrl_chars, &any_char_except]);
+fn rule(rules: &mut Rules, id: &str, sentence: Vec<Clause>) {
+    rules.insert(String::from(id), GrammarRule { branch_fn: None, sentence, left_recursive: None, operators: None, recovery: None });
+}
+
+fn map<F>(rules: &mut Rules, id: &str, branch_fn: F, sentence: Vec<Clause>)
+where F: Fn(Vec<ParseData>, &str) -> Result<ParseData, String> + Send + Sync + 'static {
+    rules.insert(String::from(id), GrammarRule { branch_fn: Some(BranchFn::Plain(Arc::new(branch_fn))), sentence, left_recursive: None, operators: None, recovery: None });
+}
 
-    let err_msg = Rule::new(f);
-    err_msg.none_or_many(&chr);
-    
-    let rule = Rule::default();
-    rule.literal("@").one(&err_msg).literal("@");
-    rule
-}
\ No newline at end of file
+fn one(id: &str) -> Clause {
+    between(id, 1, 1)
+}
+
+fn maybe(id: &str) -> Clause {
+    between(id, 0, 1)
+}
+
+fn between(id: &str, min: u64, max: u64) -> Clause {
+    Clause::Id { lookahead: None, name: String::from(id), capture: None, min, max }
+}
+
+fn any_of_ids(ids: &[&str]) -> Clause {
+    let sentences = ids.iter()
+        .map(|id| vec![one(id)])
+        .collect();
+
+    Clause::AnyOf { lookahead: None, sentences, min: 1, max: 1 }
+}
+
+fn literal_text(text: &str) -> Clause {
+    Clause::Literal { lookahead: None, text: String::from(text), ignore_case: false, min: 1, max: 1 }
+}
+
+// A rule that produces a single `ParseData::Char` of an escaped char or any char except the given ones.
+fn char_rule(rules: &mut Rules, id: &str, chars: Vec<char>) {
+    let f = |mut b: Vec<ParseData>, l: &str| {
+        match b.pop() {
+            Some(escaped) => Ok(escaped),
+            None => Ok(ParseData::Char(l.chars().next().unwrap())),
+        }
+    };
+
+    map(rules, id, f, vec![escaped_or_any_char_except(chars)]);
+}
+
+// The chars of the `ParseData::Char` values of a `char_rule`.
+fn text(b: Vec<ParseData>) -> String {
+    b.into_iter().map(|x| x.unwrap_char()).collect()
+}
+
+// Either an escaped char or any char except the given ones.
+fn escaped_or_any_char_except(chars: Vec<char>) -> Clause {
+    let replacements = ESC_CTRL_CHARS.iter()
+        .chain(ESC_CHARS.iter())
+        .map(|&(find, replace)| AlterText { find: String::from(find), replace: String::from(replace) })
+        .collect();
+
+    let escaped = Clause::AlterTexts { replacements, ignore_case: false, min: 1, max: 1 };
+    let any_other = Clause::AnyCharExcept { lookahead: None, chars, folded: None, min: 1, max: 1 };
+    Clause::AnyOf { lookahead: None, sentences: vec![vec![one("char-escape")], vec![escaped], vec![any_other]], min: 1, max: 1 }
+}
@@ -0,0 +1,1089 @@
+// Copyright (c) 2015-2023 Vincent van Ingen <code@abitvin.com>
+// Licensed under the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use super::analysis::{LeftRecursive, Tail};
+use super::ast::{sorted_ranges_contain, Clause, Lookahead};
+use super::captures::Captures;
+use super::error::{Expected, GrammarError, Position};
+use super::operators::{Assoc, Fixity, OperatorTable};
+use super::span::Span;
+use super::tree::Node;
+use super::{BranchFn, GrammarRules, Recovery};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+// Why a scan was aborted, as opposed to a clause that simply did not match.
+enum Abort {
+    Branch { rule: String, msg: String },
+    NoBacktrack { index: usize, rule: String, msg: String },
+}
+
+type Step = Result<bool, Abort>;
+
+struct Failure<'a> {
+    index: usize,
+    rule: Option<&'a str>,
+    expected: Vec<Expected>,
+}
+
+// A rule at a position, and whether it was tried inside a lookahead or whitespace.
+type MemoKey<'a> = (&'a str, usize, bool);
+
+// What a rule did at a position, so it can be replayed instead of scanned again. A success only keeps the text and
+// values the rule produced itself, for those of the rules it called it refers to their own memos.
+enum Memo<'a, T> {
+    Failure,
+    Success { index: usize, parts: Vec<Part<'a, T>>, nodes: Vec<Node>, diagnostics: Vec<GrammarError> },
+}
+
+// A piece of a remembered success, in the order it was scanned. A call is replayed without its values when the rule
+// made values of its own out of them.
+enum Part<'a, T> {
+    Text(String),
+    Values(Vec<(T, Span)>),
+    Call(MemoKey<'a>, bool),
+}
+
+// A remembered rule call within the rule being scanned, with where its text and values are.
+struct Call<'a> {
+    key: MemoKey<'a>,
+    lexeme: Range<usize>,
+    branches: Range<usize>,
+}
+
+/// Counters of a single scan. `memo_hits` is how often a rule result was replayed from the memo table instead of
+/// scanned again, `memo_misses` how often a rule result had to be scanned and stored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScanStats {
+    pub memo_hits: usize,
+    pub memo_misses: usize,
+}
+
+// The values a capture like `<lhs:expr>` named, as indexes of the branch stack.
+struct Capture<'a> {
+    name: &'a str,
+    values: Range<usize>,
+    span: Span,
+}
+
+// Where the scan is, to backtrack to.
+#[derive(Clone, Copy)]
+struct State {
+    index: usize,
+    lexeme_len: usize,
+    branches_len: usize,
+    nodes_len: usize,
+    trivia_len: usize,
+    diagnostics_len: usize,
+    captures_len: usize,
+    calls_len: usize,
+}
+
+pub struct Scanner<'a, T, C> {
+    rules: &'a GrammarRules<T, C>,
+    ws: &'a [Clause],
+    code: &'a str,
+    index: usize,
+    lexeme: String,
+    branches: Vec<(T, Span)>,
+    stack: Vec<&'a str>,
+    failure: Failure<'a>,
+    quiet: usize,
+    memo_clone: Option<fn(&T) -> T>,
+    context_rules: Option<&'a HashSet<String>>,
+    memo: HashMap<MemoKey<'a>, Memo<'a, T>>,
+    calls: Vec<Call<'a>>,
+    stats: ScanStats,
+    ctx: Option<&'a mut C>,
+    tree: bool,
+    nodes: Vec<Node>,
+    trivia: Vec<Span>,
+    recovering: bool,
+    recover_at: HashSet<(&'a str, usize)>,
+    next_recovery: Option<(&'a str, usize, usize)>,
+    diagnostics: Vec<GrammarError>,
+    hit_end: bool,
+    captures: Vec<Capture<'a>>,
+}
+
+impl<'a, T, C> Scanner<'a, T, C> {
+    pub fn new(rules: &'a GrammarRules<T, C>, ws: &'a [Clause], code: &'a str, ctx: Option<&'a mut C>) -> Self {
+        Self {
+            rules,
+            ws,
+            code,
+            index: 0,
+            lexeme: String::new(),
+            branches: vec![],
+            stack: vec![],
+            failure: Failure { index: 0, rule: None, expected: vec![] },
+            quiet: 0,
+            memo_clone: None,
+            context_rules: None,
+            memo: HashMap::new(),
+            calls: vec![],
+            stats: ScanStats::default(),
+            ctx,
+            tree: false,
+            nodes: vec![],
+            trivia: vec![],
+            recovering: false,
+            recover_at: HashSet::new(),
+            next_recovery: None,
+            diagnostics: vec![],
+            hit_end: false,
+            captures: vec![],
+        }
+    }
+
+    // Remembers the result of every rule at every position it is tried, a replayed result needs the values it
+    // produced to be cloned. The `context_rules` are always scanned, their branch functions change the context.
+    pub fn memoize(mut self, memo_clone: Option<fn(&T) -> T>, context_rules: &'a HashSet<String>) -> Self {
+        self.memo_clone = memo_clone;
+        self.context_rules = Some(context_rules);
+        self
+    }
+
+    pub fn scan(mut self, root_id: &'a str) -> Result<Vec<T>, GrammarError> {
+        self.run(root_id)
+    }
+
+    pub fn scan_with_stats(mut self, root_id: &'a str) -> (Result<Vec<T>, GrammarError>, ScanStats) {
+        let result = self.run(root_id);
+        (result, self.stats)
+    }
+
+    // Scans without calling any branch function, instead every rule that matched becomes a node of the tree.
+    pub fn parse_tree(mut self, root_id: &'a str) -> Result<Node, GrammarError> {
+        self.tree = true;
+        self.run(root_id)?;
+        Ok(self.nodes.pop().unwrap())
+    }
+
+    // Like `scan`, but rules that fail and can recover do so. Every failure becomes a diagnostic.
+    pub fn scan_recover(mut self, root_id: &'a str) -> (Vec<T>, Vec<GrammarError>) {
+        self.recovering = true;
+
+        match self.run_recovering(root_id) {
+            Ok(branches) => (branches, self.diagnostics),
+            Err(err) => {
+                self.diagnostics.push(err);
+                (vec![], self.diagnostics)
+            },
+        }
+    }
+
+    pub fn parse_tree_recover(mut self, root_id: &'a str) -> (Option<Node>, Vec<GrammarError>) {
+        self.tree = true;
+        self.recovering = true;
+
+        match self.run_recovering(root_id) {
+            Ok(_) => (self.nodes.pop(), self.diagnostics),
+            Err(err) => {
+                self.diagnostics.push(err);
+                (None, self.diagnostics)
+            },
+        }
+    }
+
+    // Scans the root rule at `start` without requiring it to reach the end of the input. On a match returns the
+    // values and where the match ended.
+    pub fn match_at(mut self, root_id: &'a str, start: usize) -> Result<Option<(Vec<T>, usize)>, GrammarError> {
+        if self.run_at(root_id, start)? {
+            Ok(Some((self.take_branches(), self.index)))
+        }
+        else {
+            Ok(None)
+        }
+    }
+
+    // Like `match_at`, but fails with the furthest failure when there is no match. Returns how much was consumed.
+    pub fn scan_prefix(self, root_id: &'a str, start: usize) -> Result<(Vec<T>, usize), GrammarError> {
+        self.scan_partial(root_id, start).0
+    }
+
+    // Like `scan_prefix`, but also tells whether the scan looked at the end of the input. When it did not, more input
+    // after the end cannot change the result.
+    pub fn scan_partial(mut self, root_id: &'a str, start: usize) -> (Result<(Vec<T>, usize), GrammarError>, bool) {
+        let result = match self.run_at(root_id, start) {
+            Ok(true) => Ok((self.take_branches(), self.index - start)),
+            Ok(false) => Err(self.parse_error()),
+            Err(err) => Err(err),
+        };
+
+        (result, self.hit_end)
+    }
+
+    fn run(&mut self, root_id: &'a str) -> Result<Vec<T>, GrammarError> {
+        let is_match = self.run_at(root_id, 0)?;
+
+        if is_match && self.index == self.code.len() {
+            return Ok(self.take_branches());
+        }
+
+        if is_match {
+            self.stack.push(root_id);
+            self.expect(Expected::Eof);
+        }
+
+        Err(self.parse_error())
+    }
+
+    // Runs the scan until it matches, each time with one more rule to recover from: the first recoverable rule that
+    // failed at the furthest failure of the run before. A rule is not recovered from anywhere else, because the rules
+    // around it may still match without it, e.g. at the end of a repetition.
+    fn run_recovering(&mut self, root_id: &'a str) -> Result<Vec<T>, GrammarError> {
+        loop {
+            let err = match self.run(root_id) {
+                Ok(branches) => return Ok(branches),
+                Err(err) => err,
+            };
+
+            match self.next_recovery.take() {
+                Some((id, start, index)) if index == self.failure.index && matches!(err, GrammarError::Parse { .. }) => {
+                    self.recover_at.insert((id, start));
+                    self.reset();
+                },
+                _ => return Err(err),
+            }
+        }
+    }
+
+    // Forgets everything of a previous run except for the rules to recover from.
+    fn reset(&mut self) {
+        self.lexeme.clear();
+        self.branches.clear();
+        self.stack.clear();
+        self.failure = Failure { index: 0, rule: None, expected: vec![] };
+        self.memo.clear();
+        self.calls.clear();
+        self.nodes.clear();
+        self.trivia.clear();
+        self.diagnostics.clear();
+        self.hit_end = false;
+        self.captures.clear();
+    }
+
+    fn run_at(&mut self, root_id: &'a str, start: usize) -> Result<bool, GrammarError> {
+        self.index = start;
+        self.failure.index = start;
+
+        match self.rule(root_id) {
+            Ok(is_match) => Ok(is_match),
+            Err(Abort::Branch { rule, msg }) => Err(GrammarError::Branch { rule, msg }),
+            Err(Abort::NoBacktrack { index, rule, msg }) => {
+                let position = Position::locate(self.code, index);
+                Err(GrammarError::NoBacktrack { position, rule, msg })
+            },
+        }
+    }
+
+    fn take_branches(&mut self) -> Vec<T> {
+        self.branches.drain(..).map(|x| x.0).collect()
+    }
+
+    // The error of the furthest failure.
+    fn parse_error(&mut self) -> GrammarError {
+        let position = Position::locate(self.code, self.failure.index);
+        let rule = self.failure.rule.map(String::from);
+        GrammarError::parse(position, rule, std::mem::take(&mut self.failure.expected))
+    }
+
+    fn rule(&mut self, id: &'a str) -> Step {
+        let memo_clone = match self.memo_clone {
+            Some(memo_clone) if !self.context_rules.is_some_and(|x| x.contains(id)) => memo_clone,
+            _ => return self.scan_rule(id),
+        };
+
+        // Failures inside a lookahead or whitespace are not recorded, so they are kept apart from the others.
+        let key = (id, self.index, self.quiet > 0);
+
+        match self.memo.get(&key) {
+            Some(Memo::Failure) => {
+                self.stats.memo_hits += 1;
+                self.expect_rule(id, self.index);
+                return Ok(false);
+            },
+            Some(Memo::Success { index, parts, nodes, diagnostics }) => {
+                let state = self.save();
+                self.stats.memo_hits += 1;
+                self.index = *index;
+                replay(&self.memo, parts, true, memo_clone, &mut self.lexeme, &mut self.branches);
+                self.nodes.extend_from_slice(nodes);
+                self.diagnostics.extend_from_slice(diagnostics);
+                self.called(key, state);
+                return Ok(true);
+            },
+            None => {},
+        }
+
+        let state = self.save();
+        let is_match = self.scan_rule(id)?;
+
+        let memo = if is_match {
+            let rule = &self.rules[id];
+            let own_values = rule.branch_fn.is_some() || rule.operators.is_some() || rule.left_recursive.is_some();
+
+            Memo::Success {
+                index: self.index,
+                parts: self.parts(state, !own_values, memo_clone),
+                nodes: self.nodes[state.nodes_len..].to_vec(),
+                diagnostics: self.diagnostics[state.diagnostics_len..].to_vec(),
+            }
+        }
+        else {
+            Memo::Failure
+        };
+
+        self.stats.memo_misses += 1;
+        self.memo.insert(key, memo);
+
+        if is_match {
+            self.called(key, state);
+        }
+
+        Ok(is_match)
+    }
+
+    // What the rule that matched since `state` produced itself, around the remembered rules it called. The values of
+    // those calls are left out when `values` is false.
+    fn parts(&self, state: State, values: bool, memo_clone: fn(&T) -> T) -> Vec<Part<'a, T>> {
+        let mut parts = vec![];
+        let mut lexeme_at = state.lexeme_len;
+        let mut branches_at = state.branches_len;
+
+        let clone_values = |range: Range<usize>| {
+            Part::Values(self.branches[range].iter().map(|(x, span)| (memo_clone(x), *span)).collect())
+        };
+
+        for call in &self.calls[state.calls_len..] {
+            if call.lexeme.start > lexeme_at {
+                parts.push(Part::Text(String::from(&self.lexeme[lexeme_at..call.lexeme.start])));
+            }
+
+            if values && call.branches.start > branches_at {
+                parts.push(clone_values(branches_at..call.branches.start));
+            }
+
+            parts.push(Part::Call(call.key, values));
+            lexeme_at = call.lexeme.end;
+            branches_at = call.branches.end;
+        }
+
+        if self.lexeme.len() > lexeme_at {
+            parts.push(Part::Text(String::from(&self.lexeme[lexeme_at..])));
+        }
+
+        if !values {
+            branches_at = state.branches_len;
+        }
+
+        if self.branches.len() > branches_at {
+            parts.push(clone_values(branches_at..self.branches.len()));
+        }
+
+        parts
+    }
+
+    // Replaces the remembered calls made since `state` with the call of the rule that matched since then.
+    fn called(&mut self, key: MemoKey<'a>, state: State) {
+        self.calls.truncate(state.calls_len);
+        self.calls.push(Call { key, lexeme: state.lexeme_len..self.lexeme.len(), branches: state.branches_len..self.branches.len() });
+    }
+
+    fn scan_rule(&mut self, id: &'a str) -> Step {
+        let rule = &self.rules[id];
+        let state = self.save();
+        let start = self.index;
+
+        self.stack.push(id);
+
+        let is_match = match (&rule.operators, &rule.left_recursive) {
+            (Some(ref operators), _) => self.climb(id, operators, Some(0))?,
+            (None, Some(ref left_recursive)) => self.grow(id, left_recursive, state)?,
+            (None, None) => self.sentence(&rule.sentence)?,
+        };
+
+        self.stack.pop();
+
+        if !is_match {
+            self.expect_rule(id, start);
+
+            return match rule.recovery {
+                Some(ref recovery) if self.recovering && self.quiet == 0 => {
+                    if self.recover_at.contains(&(id, start)) {
+                        return self.recover(id, recovery);
+                    }
+
+                    if self.next_recovery.is_none_or(|x| x.2 < self.failure.index) {
+                        self.next_recovery = Some((id, start, self.failure.index));
+                    }
+
+                    Ok(false)
+                },
+                _ => Ok(false),
+            };
+        }
+
+        if rule.left_recursive.is_none() {
+            self.branch(id, state)?;
+        }
+
+        Ok(true)
+    }
+
+    // Skips the input up to and including the next match of the recovery expression, or up to the end of the input,
+    // and makes an error value or node of the skipped text. The furthest failure becomes a diagnostic and the failures
+    // are tracked anew from where the scan continues.
+    fn recover(&mut self, id: &'a str, recovery: &'a Recovery<T>) -> Step {
+        let start = self.index;
+
+        if start == self.code.len() {
+            return Ok(false);
+        }
+
+        let mut end = self.code.len();
+        let mut sync = start;
+
+        while sync < self.code.len() {
+            let state = self.save();
+            self.index = sync;
+            self.quiet += 1;
+            let is_match = self.sentence(&recovery.sentence);
+            self.quiet -= 1;
+            let sync_end = self.index;
+            self.restore(state);
+
+            if is_match? && sync_end > start {
+                end = sync_end;
+                break;
+            }
+
+            sync += self.code[sync..].chars().next().map_or(1, char::len_utf8);
+        }
+
+        let diagnostic = self.parse_error();
+        self.diagnostics.push(diagnostic);
+        self.failure = Failure { index: end, rule: None, expected: vec![] };
+        self.next_recovery = None;
+
+        let span = Span { start, end };
+        let text = &self.code[start..end];
+        self.index = end;
+        self.lexeme.push_str(text);
+
+        if self.tree {
+            self.node(id, span, vec![]);
+            self.nodes.last_mut().unwrap().error = true;
+        }
+        else {
+            self.branches.push(((recovery.error_fn)(text), span));
+        }
+
+        Ok(true)
+    }
+
+    // When the rule got nowhere, the rule itself is expected as well as whatever inside it was.
+    fn expect_rule(&mut self, id: &'a str, start: usize) {
+        if self.failure.index == start {
+            self.expect(Expected::Rule(String::from(id)));
+        }
+    }
+
+    // Scans a left recursive rule. A seed is matched first and then the tails are matched after it for as long as
+    // they can, as if the rule call each tail started with matched everything scanned so far.
+    fn grow(&mut self, id: &'a str, left_recursive: &'a LeftRecursive, state: State) -> Step {
+        if !self.any_sentence(&left_recursive.seeds)? {
+            return Ok(false);
+        }
+
+        self.branch(id, state)?;
+
+        loop {
+            let index = self.index;
+
+            if !self.tail(&left_recursive.tails, state)? {
+                return Ok(true);
+            }
+
+            self.branch(id, state)?;
+
+            if self.index == index {
+                return Ok(true);
+            }
+        }
+    }
+
+    // Matches one of the tails of a left recursive rule. The values of the match so far, since `start`, are captured
+    // by the capture of the tail.
+    fn tail(&mut self, tails: &'a [Tail], start: State) -> Step {
+        for tail in tails {
+            let captures_len = self.captures.len();
+
+            if let Some(ref name) = tail.capture {
+                let capture = Capture { name, values: start.branches_len..self.branches.len(), span: Span { start: start.index, end: self.index } };
+                self.captures.push(capture);
+            }
+
+            if self.sentence(&tail.sentence)? {
+                return Ok(true);
+            }
+
+            self.captures.truncate(captures_len);
+        }
+
+        Ok(false)
+    }
+
+    // Scans an operators rule by precedence climbing. Only operators with a precedence of at least `min` are taken, none
+    // when `min` is `None`. On a match exactly one value is left on the branch stack.
+    fn climb(&mut self, id: &'a str, table: &'a OperatorTable<T>, min: Option<u32>) -> Step {
+        let start = self.save();
+
+        if !self.prefix(id, table)? && !self.operand(id, table)? {
+            return Ok(false);
+        }
+
+        // The precedence of the last non associative operator, which may not be followed by another one of the same.
+        let mut non_assoc = None;
+
+        'grow: loop {
+            for op in &table.ops {
+                if min.is_none_or(|min| op.precedence < min) || non_assoc == Some(op.precedence) {
+                    continue;
+                }
+
+                let state = self.save();
+
+                match op.fixity {
+                    Fixity::Prefix(_) => {},
+                    Fixity::Postfix(ref combine_fn) => {
+                        if !self.operator(&op.sentence)? {
+                            continue;
+                        }
+
+                        let op_lexeme = (state.lexeme_len, self.lexeme.len());
+                        self.combine(id, start, 1, op_lexeme, |mut x, l| combine_fn(x.remove(0), l))?;
+                        non_assoc = None;
+                        continue 'grow;
+                    },
+                    Fixity::Infix(assoc, ref combine_fn) => {
+                        if !self.operator(&op.sentence)? {
+                            continue;
+                        }
+
+                        let op_lexeme = (state.lexeme_len, self.lexeme.len());
+                        // Nothing binds tighter than the highest precedence.
+                        let next = if assoc == Assoc::Right { Some(op.precedence) } else { op.precedence.checked_add(1) };
+
+                        if !self.climb(id, table, next)? {
+                            self.restore(state);
+                            continue;
+                        }
+
+                        self.combine(id, start, 2, op_lexeme, |mut x, l| {
+                            let right = x.pop().unwrap();
+                            combine_fn(x.pop().unwrap(), right, l)
+                        })?;
+                        non_assoc = if assoc == Assoc::None { Some(op.precedence) } else { None };
+                        continue 'grow;
+                    },
+                }
+            }
+
+            return Ok(true);
+        }
+    }
+
+    // Tries the prefix operators, the operand of a prefix operator takes every operator that binds tighter.
+    fn prefix(&mut self, id: &'a str, table: &'a OperatorTable<T>) -> Step {
+        for op in &table.ops {
+            if let Fixity::Prefix(ref combine_fn) = op.fixity {
+                let state = self.save();
+
+                if !self.operator(&op.sentence)? {
+                    continue;
+                }
+
+                let op_lexeme = (state.lexeme_len, self.lexeme.len());
+
+                if !self.climb(id, table, Some(op.precedence))? {
+                    self.restore(state);
+                    continue;
+                }
+
+                self.combine(id, state, 1, op_lexeme, |mut x, l| combine_fn(x.remove(0), l))?;
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn operand(&mut self, id: &'a str, table: &'a OperatorTable<T>) -> Step {
+        let branches_len = self.branches.len();
+
+        if !self.rule(&table.operand)? {
+            return Ok(false);
+        }
+
+        if !self.tree && self.branches.len() != branches_len + 1 {
+            return Err(Abort::Branch {
+                rule: String::from(id),
+                msg: format!("The operand \"{}\" of rule \"{}\" must produce exactly one value.", table.operand, id),
+            });
+        }
+
+        Ok(true)
+    }
+
+    // Replaces the last `count` values, the operands of an operator, with the result of the operator.
+    fn combine<F>(&mut self, id: &'a str, start: State, count: usize, op_lexeme: (usize, usize), combine_fn: F) -> Result<(), Abort>
+    where F: FnOnce(Vec<T>, &str) -> Result<T, String> {
+        let span = Span { start: start.index, end: self.index };
+
+        if self.tree {
+            let mut children = self.nodes.split_off(self.nodes.len() - count);
+            let trivia = self.trivia.split_off(start.trivia_len);
+            self.attach(&mut children, trivia);
+            self.node(id, span, children);
+            return Ok(());
+        }
+
+        let operands = self.branches.drain(self.branches.len() - count..).map(|x| x.0).collect();
+        let value = combine_fn(operands, &self.lexeme[op_lexeme.0..op_lexeme.1])
+            .map_err(|msg| Abort::Branch { rule: String::from(id), msg })?;
+        self.branches.push((value, span));
+        Ok(())
+    }
+
+    // Matches an operator, whatever it produces is dropped.
+    fn operator(&mut self, sentence: &'a [Clause]) -> Step {
+        let state = self.save();
+        let is_match = self.sentence(sentence)?;
+        self.branches.truncate(state.branches_len);
+        self.nodes.truncate(state.nodes_len);
+        self.captures.truncate(state.captures_len);
+        Ok(is_match)
+    }
+
+    // Replaces the branches produced since `branches_len` with the result of the branch function of the rule. When
+    // building a tree the nodes produced since `nodes_len` become the children of the node of the rule instead.
+    fn branch(&mut self, id: &'a str, state: State) -> Result<(), Abort> {
+        let span = Span { start: state.index, end: self.index };
+
+        // The captures are those of the expression of the rule, the rules it called took theirs already.
+        let captures = self.captures.split_off(state.captures_len);
+
+        if self.tree {
+            // An operators rule that applied an operator already made the node of the whole match.
+            let is_combined = self.nodes.len() == state.nodes_len + 1
+                && self.nodes[state.nodes_len].rule == id
+                && self.nodes[state.nodes_len].span == span;
+
+            let mut children = self.nodes.split_off(state.nodes_len);
+            let trivia = self.trivia.split_off(state.trivia_len);
+
+            if is_combined {
+                self.nodes.append(&mut children);
+            }
+            else {
+                self.attach(&mut children, trivia);
+                self.node(id, span, children);
+            }
+
+            return Ok(());
+        }
+
+        let branches_len = state.branches_len;
+        let lexeme = &self.lexeme[state.lexeme_len..];
+
+        let branch = match self.rules[id].branch_fn {
+            Some(BranchFn::Plain(ref branch_fn)) => branch_fn(self.branches.drain(branches_len..).map(|x| x.0).collect(), lexeme),
+            Some(BranchFn::Spanned(ref branch_fn)) => branch_fn(self.branches.split_off(branches_len), lexeme, span),
+            Some(BranchFn::Context(ref branch_fn)) => {
+                let ctx = self.ctx.as_deref_mut().expect("A scan with branch functions has a context");
+                branch_fn(self.branches.drain(branches_len..).map(|x| x.0).collect(), lexeme, ctx)
+            },
+            Some(BranchFn::Named(ref branch_fn)) => {
+                let values = self.branches.drain(branches_len..).map(|x| x.0).collect();
+
+                let captures = captures
+                    .into_iter()
+                    .map(|x| (x.name, x.values.start - branches_len..x.values.end - branches_len, x.span))
+                    .collect();
+
+                branch_fn(Captures::new(self.code, values, captures), lexeme)
+            },
+            None => return Ok(()),
+        };
+
+        let branch = branch.map_err(|msg| Abort::Branch { rule: String::from(id), msg })?;
+        self.branches.push((branch, span));
+        Ok(())
+    }
+
+    fn node(&mut self, id: &'a str, span: Span, children: Vec<Node>) {
+        let text = String::from(&self.code[span.start..span.end]);
+        self.nodes.push(Node { rule: String::from(id), span, text, children, leading: String::new(), trailing: String::new(), error: false });
+    }
+
+    // Gives the children of a node the whitespace around them. Whitespace right after a child is its trailing trivia up
+    // to and including the first line break when another child follows, the rest is the leading trivia of that child.
+    // Whitespace next to no child stays part of the text of the node.
+    fn attach(&self, children: &mut [Node], trivia: Vec<Span>) {
+        let mut merged: Vec<Span> = vec![];
+
+        for x in trivia {
+            match merged.last_mut() {
+                Some(last) if last.end == x.start => last.end = x.end,
+                _ => merged.push(x),
+            }
+        }
+
+        for x in merged {
+            let mut text = &self.code[x.start..x.end];
+            let next = children.iter().position(|c| c.span.start == x.end);
+
+            if let Some(i) = children.iter().position(|c| c.span.end == x.start) {
+                let split = match (next, text.find('\n')) {
+                    (Some(_), Some(line_break)) => line_break + 1,
+                    _ => text.len(),
+                };
+
+                children[i].trailing = String::from(&text[..split]);
+                text = &text[split..];
+            }
+
+            if let Some(i) = next.filter(|_| !text.is_empty()) {
+                children[i].leading = String::from(text);
+            }
+        }
+    }
+
+    fn any_sentence(&mut self, sentences: &'a [Vec<Clause>]) -> Step {
+        for sentence in sentences {
+            if self.sentence(sentence)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn sentence(&mut self, sentence: &'a [Clause]) -> Step {
+        let state = self.save();
+        let mut no_backtrack = None;
+
+        for clause in sentence {
+            if let Clause::NoBacktrack(ref err_msg) = clause {
+                no_backtrack = Some(err_msg);
+                continue;
+            }
+
+            if !self.clause(clause)? {
+                if let Some(err_msg) = no_backtrack {
+                    return Err(Abort::NoBacktrack {
+                        index: self.index,
+                        rule: self.stack.last().map(|x| x.to_string()).unwrap_or_default(),
+                        msg: err_msg.clone(),
+                    });
+                }
+
+                self.restore(state);
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn clause(&mut self, clause: &'a Clause) -> Step {
+        match clause {
+            Clause::AlterTexts { min, max, .. } => self.quantity(clause, None, *min, *max),
+            Clause::AnyChar { lookahead, min, max } => self.quantity(clause, *lookahead, *min, *max),
+            Clause::AnyCharExcept { lookahead, min, max, .. } => self.quantity(clause, *lookahead, *min, *max),
+            Clause::AnyOf { lookahead, min, max, .. } => self.quantity(clause, *lookahead, *min, *max),
+            Clause::CharRanges { lookahead, min, max, .. } => self.quantity(clause, *lookahead, *min, *max),
+            Clause::Eof => {
+                if self.index == self.code.len() {
+                    self.hit_end = true;
+                    Ok(true)
+                }
+                else {
+                    self.expect(Expected::Eof);
+                    Ok(false)
+                }
+            },
+            Clause::Id { lookahead, min, max, .. } => self.quantity(clause, *lookahead, *min, *max),
+            Clause::Literal { lookahead, min, max, .. } => self.quantity(clause, *lookahead, *min, *max),
+            Clause::NoBacktrack(_) => Ok(true),
+            Clause::Whitespace { min, max } => self.quantity(clause, None, *min, *max),
+        }
+    }
+
+    fn quantity(&mut self, clause: &'a Clause, lookahead: Option<Lookahead>, min: u64, max: u64) -> Step {
+        let state = self.save();
+
+        let is_match = match lookahead {
+            None => return self.repeat(clause, min, max),
+            Some(Lookahead::And) => self.repeat(clause, min, max),
+            Some(Lookahead::Not) => {
+                self.quiet += 1;
+                let is_match = self.repeat(clause, min, max);
+                self.quiet -= 1;
+                is_match.map(|x| !x)
+            },
+        };
+
+        self.restore(state);
+        is_match
+    }
+
+    fn repeat(&mut self, clause: &'a Clause, min: u64, max: u64) -> Step {
+        let state = self.save();
+        let mut count = 0;
+
+        while count < max {
+            let index = self.index;
+
+            if !self.once(clause)? {
+                break;
+            }
+
+            count += 1;
+
+            // An empty match would repeat forever, the remaining repetitions are empty matches as well.
+            if self.index == index {
+                count = max;
+            }
+        }
+
+        if count >= min {
+            Ok(true)
+        }
+        else {
+            self.restore(state);
+            Ok(false)
+        }
+    }
+
+    fn once(&mut self, clause: &'a Clause) -> Step {
+        match clause {
+            Clause::AlterTexts { ref replacements, ignore_case, .. } => {
+                if let Some((x, len)) = replacements.iter().find_map(|x| self.text(&x.find, *ignore_case).map(|len| (x, len))) {
+                    self.index += len;
+                    self.lexeme.push_str(&x.replace);
+                    return Ok(true);
+                }
+
+                self.expect_many(replacements.iter().map(|x| Expected::Literal(x.find.clone())));
+                Ok(false)
+            },
+            Clause::AnyChar { .. } => {
+                if self.char(|_| true) {
+                    return Ok(true);
+                }
+
+                self.expect(Expected::AnyChar);
+                Ok(false)
+            },
+            Clause::AnyCharExcept { ref chars, ref folded, .. } => {
+                let is_match = |c: char| match folded {
+                    Some(folded) => !sorted_ranges_contain(folded, c),
+                    None => !chars.contains(&c),
+                };
+
+                if self.char(is_match) {
+                    return Ok(true);
+                }
+
+                self.expect(Expected::AnyCharExcept(chars.clone()));
+                Ok(false)
+            },
+            Clause::AnyOf { ref sentences, .. } => {
+                self.any_sentence(sentences)
+            },
+            Clause::CharRanges { ref ranges, ref classes, ref folded, .. } => {
+                let is_match = |c: char| match folded {
+                    Some(folded) => sorted_ranges_contain(folded, c),
+                    None => ranges.iter().any(|r| r.start <= c && c <= r.end) || classes.iter().any(|x| x.contains(c)),
+                };
+
+                if self.char(is_match) {
+                    return Ok(true);
+                }
+
+                let expected_ranges = ranges.iter().map(|r| Expected::CharRange { start: r.start, end: r.end });
+                let expected_classes = classes.iter().map(|x| Expected::CharClass { name: x.name.clone(), negated: x.negated });
+                self.expect_many(expected_ranges.chain(expected_classes));
+                Ok(false)
+            },
+            Clause::Id { ref name, capture: None, .. } => {
+                self.rule(name)
+            },
+            Clause::Id { ref name, capture: Some(ref capture), .. } => {
+                let state = self.save();
+
+                if !self.rule(name)? {
+                    return Ok(false);
+                }
+
+                let span = Span { start: state.index, end: self.index };
+                self.captures.push(Capture { name: capture, values: state.branches_len..self.branches.len(), span });
+                Ok(true)
+            },
+            Clause::Literal { ref text, ignore_case, .. } => {
+                if let Some(len) = self.text(text, *ignore_case) {
+                    self.lexeme.push_str(&self.code[self.index..self.index + len]);
+                    self.index += len;
+                    return Ok(true);
+                }
+
+                self.expect(Expected::Literal(text.clone()));
+                Ok(false)
+            },
+            Clause::Whitespace { .. } => {
+                let state = self.save();
+                self.quiet += 1;
+                let is_match = self.sentence(self.ws);
+                self.quiet -= 1;
+
+                if let Ok(false) = is_match {
+                    self.expect(Expected::Whitespace);
+                }
+
+                // The rules matched by the whitespace are no part of the tree, the whitespace becomes trivia.
+                if self.tree && self.index > state.index {
+                    self.nodes.truncate(state.nodes_len);
+                    self.trivia.truncate(state.trivia_len);
+                    self.trivia.push(Span { start: state.index, end: self.index });
+                }
+
+                is_match
+            },
+            Clause::Eof | Clause::NoBacktrack(_) => unreachable!("Clause {:?} has no quantity", clause),
+        }
+    }
+
+    fn char<F>(&mut self, is_valid: F) -> bool
+    where F: Fn(char) -> bool {
+        match self.code[self.index..].chars().next() {
+            Some(c) if is_valid(c) => {
+                self.index += c.len_utf8();
+                self.lexeme.push(c);
+                true
+            },
+            Some(_) => false,
+            None => {
+                self.hit_end = true;
+                false
+            },
+        }
+    }
+
+    // The length of the input at the index that is `text`, if it is there. The end of the input can cut the text
+    // short, then more input could make it match.
+    fn text(&mut self, text: &str, ignore_case: bool) -> Option<usize> {
+        let rest = &self.code[self.index..];
+
+        if !ignore_case {
+            if rest.starts_with(text) {
+                return Some(text.len());
+            }
+
+            if text.starts_with(rest) {
+                self.hit_end = true;
+            }
+
+            return None;
+        }
+
+        let mut input = rest.chars();
+        let mut len = 0;
+
+        for x in text.chars() {
+            match input.next() {
+                Some(c) if fold_case(c) == fold_case(x) => len += c.len_utf8(),
+                Some(_) => return None,
+                None => {
+                    self.hit_end = true;
+                    return None;
+                },
+            }
+        }
+
+        Some(len)
+    }
+
+    fn expect(&mut self, expected: Expected) {
+        self.expect_many(::std::iter::once(expected));
+    }
+
+    fn expect_many<I>(&mut self, expected: I)
+    where I: Iterator<Item = Expected> {
+        if self.quiet > 0 || self.index < self.failure.index {
+            return;
+        }
+
+        if self.index > self.failure.index || self.failure.expected.is_empty() {
+            self.failure.index = self.index;
+            self.failure.rule = self.stack.last().copied();
+            self.failure.expected.clear();
+        }
+
+        self.failure.expected.extend(expected);
+    }
+
+    fn save(&self) -> State {
+        State {
+            index: self.index,
+            lexeme_len: self.lexeme.len(),
+            branches_len: self.branches.len(),
+            nodes_len: self.nodes.len(),
+            trivia_len: self.trivia.len(),
+            diagnostics_len: self.diagnostics.len(),
+            captures_len: self.captures.len(),
+            calls_len: self.calls.len(),
+        }
+    }
+
+    fn restore(&mut self, state: State) {
+        self.index = state.index;
+        self.lexeme.truncate(state.lexeme_len);
+        self.branches.truncate(state.branches_len);
+        self.nodes.truncate(state.nodes_len);
+        self.trivia.truncate(state.trivia_len);
+        self.diagnostics.truncate(state.diagnostics_len);
+        self.captures.truncate(state.captures_len);
+        self.calls.truncate(state.calls_len);
+    }
+}
+
+// Replays the text and, when `values`, the values of a remembered success.
+fn replay<T>(memo: &HashMap<MemoKey, Memo<T>>, parts: &[Part<T>], values: bool, memo_clone: fn(&T) -> T, lexeme: &mut String, branches: &mut Vec<(T, Span)>) {
+    for part in parts {
+        match part {
+            Part::Text(text) => lexeme.push_str(text),
+            Part::Values(x) if values => branches.extend(x.iter().map(|(x, span)| (memo_clone(x), *span))),
+            Part::Values(_) => {},
+            Part::Call(key, with_values) => {
+                if let Some(Memo::Success { parts, .. }) = memo.get(key) {
+                    replay(memo, parts, values && *with_values, memo_clone, lexeme, branches);
+                }
+            },
+        }
+    }
+}
+
+// The lower case of the upper case of a char, when both are a single char. Chars that only differ in case, like `s`,
+// `S` and `ſ` or `k` and `K` (Kelvin), fold to the same char.
+fn fold_case(c: char) -> char {
+    let upper = single_char(c.to_uppercase()).unwrap_or(c);
+    single_char(upper.to_lowercase()).unwrap_or(upper)
+}
+
+fn single_char(mut chars: impl ExactSizeIterator<Item = char>) -> Option<char> {
+    if chars.len() == 1 { chars.next() } else { None }
+}
@@ -0,0 +1,83 @@
+// Copyright (c) 2015-2023 Vincent van Ingen <code@abitvin.com>
+// Licensed under the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use super::error::GrammarError;
+use super::span::Span;
+use super::CompiledGrammar;
+
+/// A part of a haystack matched by a rule, with the values the rule produced.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match<'a, T> {
+    pub span: Span,
+    pub text: &'a str,
+    pub values: Vec<T>,
+}
+
+/// The matches of a rule in a haystack that do not overlap, see `CompiledGrammar::find_iter`. The iterator ends after
+/// the first error.
+pub struct FindIter<'a, T, C> {
+    grammar: &'a CompiledGrammar<T, C>,
+    root_id: &'a str,
+    haystack: &'a str,
+    ctx: C,
+    offset: usize,
+    last_end: Option<usize>,
+}
+
+impl<'a, T, C> FindIter<'a, T, C> {
+    pub(crate) fn new(grammar: &'a CompiledGrammar<T, C>, root_id: &'a str, haystack: &'a str, ctx: C) -> Self {
+        Self {
+            grammar,
+            root_id,
+            haystack,
+            ctx,
+            offset: 0,
+            last_end: None,
+        }
+    }
+
+    fn advance(&mut self) {
+        self.offset += self.haystack[self.offset..].chars().next().map_or(1, char::len_utf8);
+    }
+}
+
+impl<'a, T, C> Iterator for FindIter<'a, T, C> {
+    type Item = Result<Match<'a, T>, GrammarError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.offset <= self.haystack.len() {
+            let start = self.offset;
+
+            let (values, end) = match self.grammar.match_at(self.root_id, self.haystack, start, &mut self.ctx) {
+                Ok(Some(x)) => x,
+                Ok(None) => {
+                    self.advance();
+                    continue;
+                },
+                Err(err) => {
+                    self.offset = self.haystack.len() + 1;
+                    return Some(Err(err));
+                },
+            };
+
+            // Like a regex, an empty match right where the previous match ended is not a match.
+            if end == start && self.last_end == Some(start) {
+                self.advance();
+                continue;
+            }
+
+            if end == start {
+                self.advance();
+            }
+            else {
+                self.offset = end;
+            }
+
+            self.last_end = Some(end);
+            return Some(Ok(Match { span: Span { start, end }, text: &self.haystack[start..end], values }));
+        }
+
+        None
+    }
+}
@@ -0,0 +1,19 @@
+// Copyright (c) 2015-2023 Vincent van Ingen <code@abitvin.com>
+// Licensed under the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use super::error::Position;
+
+/// The part of the scanned input a value was made from, as byte offsets. `end` is exclusive.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// The line and column of the start and the end of the span in `code`, which must be the scanned input.
+    pub fn locate(&self, code: &str) -> (Position, Position) {
+        (Position::locate(code, self.start), Position::locate(code, self.end))
+    }
+}
@@ -0,0 +1,42 @@
+// Copyright (c) 2015-2023 Vincent van Ingen <code@abitvin.com>
+// Licensed under the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use super::span::Span;
+use std::fmt;
+
+/// A rule that matched, with the rules that matched within it as its children. The rule of a node made by an operator
+/// of a `Grammar::operators` rule is the id of that rule and its children are the operands.
+///
+/// Whitespace scanned by the `_` and ` ` clauses is trivia: the `leading` and `trailing` trivia of a node is the
+/// whitespace right before and after it, which is not part of its `span` and `text`. Printing a node prints its trivia
+/// as well, so printing the root node gives back the scanned input byte for byte.
+///
+/// An `error` node is a rule that failed and recovered, see `Grammar::recover`. Its text is the skipped input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node {
+    pub rule: String,
+    pub span: Span,
+    pub text: String,
+    pub children: Vec<Node>,
+    pub leading: String,
+    pub trailing: String,
+    pub error: bool,
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.leading)?;
+
+        // The text of the node in between its children, like the literals and whitespace not attached to a child.
+        let mut offset = 0;
+
+        for child in &self.children {
+            let start = child.span.start - child.leading.len() - self.span.start;
+            write!(f, "{}{}", &self.text[offset..start], child)?;
+            offset = child.span.end + child.trailing.len() - self.span.start;
+        }
+
+        write!(f, "{}{}", &self.text[offset..], self.trailing)
+    }
+}
@@ -0,0 +1,44 @@
+use grammar::{Expected, Grammar, GrammarError};
+
+#[test]
+fn and() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("word", "[a-z]+", |_, l| Ok(l.to_string()));
+    grammar.map("call", "<word>&\\(", |b, _| Ok(b[0].clone()));
+    grammar.map("calls", "(<call>\\(\\)|<word>)( (<call>\\(\\)|<word>))*", |b, _| Ok(b.join(",")));
+    grammar.map("digits", "&[0-9]{3}[0-9]+", |_, l| Ok(l.to_string()));
+    grammar.map("either", "&(a|b)[a-z]", |_, l| Ok(l.to_string()));
+    grammar.map("twice", "&(ab){2}[a-z]+", |_, l| Ok(l.to_string()));
+
+    let compiled_grammar = grammar.compile().unwrap();
+
+    assert_eq!(compiled_grammar.scan("calls", "foo() bar baz()").unwrap(), vec!["foo,bar,baz"]);
+    assert_eq!(compiled_grammar.scan("digits", "1234").unwrap(), vec!["1234"]);
+    assert!(compiled_grammar.scan("digits", "12").is_err());
+    assert_eq!(compiled_grammar.scan("either", "b").unwrap(), vec!["b"]);
+    assert!(compiled_grammar.scan("either", "c").is_err());
+    assert!(compiled_grammar.scan("twice", "ababx").is_ok());
+    assert!(compiled_grammar.scan("twice", "abx").is_err());
+    assert_eq!(compiled_grammar.scan("calls", "a&").unwrap_err().position().unwrap().offset, 1);
+
+    match compiled_grammar.scan("call", "foo") {
+        Err(GrammarError::Parse { expected, .. }) => assert!(expected.contains(&Expected::Literal(String::from("(")))),
+        other => panic!("Unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn and_literal() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("and", "a\\&\\&b", |_, l| Ok(l.to_string()));
+
+    grammar.map("escaped", "R\\&D", |_, l| Ok(l.to_string()));
+    grammar.map("lookahead", "R&D[A-Z]", |_, l| Ok(l.to_string()));
+
+    let compiled_grammar = grammar.compile().unwrap();
+    assert_eq!(compiled_grammar.scan("and", "a&&b").unwrap(), vec!["a&&b"]);
+    assert_eq!(compiled_grammar.scan("escaped", "R&D").unwrap(), vec!["R&D"]);
+    assert!(compiled_grammar.scan("escaped", "RD").is_err());
+    assert_eq!(compiled_grammar.scan("lookahead", "RD").unwrap(), vec!["RD"]);
+    assert!(compiled_grammar.scan("lookahead", "R&D").is_err());
+}
@@ -1,4 +1,4 @@
-use grammar::Grammar;
+use grammar::{Grammar, GrammarError};
 
 #[test]
 fn any_char_except() {
@@ -16,4 +16,21 @@ fn any_char_except() {
     assert!(compiled.scan("test-b", "banana is love!").is_ok());
     assert!(compiled.scan("test-b", "BANANA IS LOVE!").is_err());
     assert!(compiled.scan("test-b", "banana is 💝!").is_err());
-}
\ No newline at end of file
+}
+
+#[test]
+fn any_char_except_class() {
+    let mut grammar: Grammar<i32> = Grammar::new();
+
+    for expr in &["[^\\p{L}]+", "[^a\\P{Nd}]", "[^\\p{Nope}]"] {
+        match grammar.try_rule("test", expr) {
+            Err(GrammarError::Syntax { id, .. }) => assert_eq!(id, "test"),
+            other => panic!("Unexpected {:?} for {}", other, expr),
+        }
+    }
+
+    grammar.rule("test", "[^\\p]+");
+    let compiled = grammar.compile().unwrap();
+    assert!(compiled.scan("test", "abc").is_ok());
+    assert!(compiled.scan("test", "a\\c").is_err());
+}
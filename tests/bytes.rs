@@ -0,0 +1,62 @@
+use grammar::{ByteGrammar, Grammar, GrammarError};
+
+#[derive(Debug, PartialEq)]
+enum Frame {
+    Text(Vec<u8>),
+    Data(Vec<u8>),
+    Frames(Vec<Frame>),
+}
+
+#[test]
+fn bytes() {
+    let mut grammar: ByteGrammar<Frame> = ByteGrammar::new_with_ws("\\x00");
+    grammar.map("text", "\\x01[\\x20-\\xFF]*", |_, l| Ok(Frame::Text(l[1..].to_vec())));
+    grammar.map("data", "\\x02[\\x00-\\x1F]{2}", |_, l| Ok(Frame::Data(l[1..].to_vec())));
+    grammar.map("frames", "(<text>|<data>)( (<text>|<data>))*\\x04", |b, _| Ok(Frame::Frames(b)));
+
+    let compiled_grammar = grammar.compile().unwrap();
+    let code = b"\x01caf\xE9\x00\x02\x00\x1F\x04";
+
+    match compiled_grammar.scan("frames", code).unwrap().pop() {
+        Some(Frame::Frames(frames)) => assert_eq!(frames, vec![Frame::Text(b"caf\xE9".to_vec()), Frame::Data(vec![0x00, 0x1F])]),
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    assert_eq!(compiled_grammar.scan_prefix("data", b"\x02\x10\x11\xFF\xFF").unwrap().1, 3);
+
+    match compiled_grammar.scan("frames", b"\x01\xFF\xFF\x02\x00\x20\x04") {
+        Err(GrammarError::Parse { position, .. }) => assert_eq!((position.offset, position.column), (5, 6)),
+        other => panic!("Unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn bytes_not_bytes() {
+    let mut grammar: ByteGrammar<Vec<u8>> = ByteGrammar::new();
+
+    match grammar.try_map("alter", "(~a,\\u{20AC})", |_, l| Ok(l.to_vec())) {
+        Err(GrammarError::NotBytes { rule, text }) => assert_eq!((rule.as_str(), text.as_str()), ("alter", "€")),
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    match grammar.try_rule("literal", "x(€|y)") {
+        Err(GrammarError::NotBytes { rule, text }) => assert_eq!((rule.as_str(), text.as_str()), ("literal", "€")),
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    grammar.map("alter", "(~a,\\xFF)", |_, l| Ok(l.to_vec()));
+    grammar.rule("literal", "x(é|y)");
+    let compiled_grammar = grammar.compile().unwrap();
+    assert_eq!(compiled_grammar.scan("alter", b"a").unwrap(), vec![vec![0xFF]]);
+    assert!(compiled_grammar.scan("literal", b"x\xE9").is_ok());
+}
+
+#[test]
+fn hex_escape() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("root", "\\x41[\\x61-\\x7a]+\\x2d(~\\x21,\\x3F)", |_, l| Ok(l.to_string()));
+
+    let compiled_grammar = grammar.compile().unwrap();
+    assert_eq!(compiled_grammar.scan("root", "Abc-!").unwrap(), vec!["Abc-?"]);
+    assert!(compiled_grammar.scan("root", "ABC-!").is_err());
+}
@@ -0,0 +1,53 @@
+use grammar::{Expected, Grammar, GrammarError};
+
+#[test]
+fn char_class() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("ident", "[\\p{L}_][\\p{L}\\p{Nd}_]*", |_, l| Ok(l.to_string()));
+    grammar.map("greek", "\\p{Greek}+", |_, l| Ok(l.to_string()));
+    grammar.map("not-greek", "\\P{Greek}+", |_, l| Ok(l.to_string()));
+    grammar.map("not-letter", "!\\p{L}.", |_, l| Ok(l.to_string()));
+
+    let compiled_grammar = grammar.compile().unwrap();
+
+    assert!(compiled_grammar.scan("ident", "größe_2").is_ok());
+    assert!(compiled_grammar.scan("ident", "_日本語").is_ok());
+    assert!(compiled_grammar.scan("ident", "λ٣").is_ok());
+    assert!(compiled_grammar.scan("ident", "2x").is_err());
+    assert!(compiled_grammar.scan("greek", "αβγ").is_ok());
+    assert!(compiled_grammar.scan("greek", "αbγ").is_err());
+    assert!(compiled_grammar.scan("not-greek", "abc").is_ok());
+    assert!(compiled_grammar.scan("not-greek", "aβc").is_err());
+    assert!(compiled_grammar.scan("not-letter", "1").is_ok());
+    assert!(compiled_grammar.scan("not-letter", "x").is_err());
+}
+
+#[test]
+fn char_class_errors() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.rule("word", "[\\p{Lu}a-z]+");
+
+    match grammar.compile().unwrap().scan("word", "1") {
+        Err(GrammarError::Parse { expected, .. }) => {
+            assert_eq!(expected, vec![Expected::CharRange { start: 'a', end: 'z' }, Expected::CharClass { name: String::from("Lu"), negated: false }, Expected::Rule(String::from("word"))]);
+        },
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    let mut grammar: Grammar<String> = Grammar::new();
+
+    match grammar.try_rule("word", "\\p{Klingon}+") {
+        Err(GrammarError::Syntax { source, .. }) => assert_eq!(source.to_string(), "Unknown Unicode property \"Klingon\"."),
+        other => panic!("Unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn char_class_after_literal() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.rule("var", "var\\p{Nd}+");
+
+    let compiled_grammar = grammar.compile().unwrap();
+    assert!(compiled_grammar.scan("var", "var42").is_ok());
+    assert!(compiled_grammar.scan("var", "var").is_err());
+}
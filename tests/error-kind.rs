@@ -0,0 +1,49 @@
+use grammar::{Grammar, GrammarError};
+
+#[test]
+fn error_kind() {
+    let mut grammar: Grammar<i32> = Grammar::new();
+    grammar.rule("root", "<monkey>");
+
+    match grammar.compile().err() {
+        Some(GrammarError::UnknownRules(unknown)) => {
+            assert_eq!(unknown.len(), 1);
+            assert_eq!(unknown[0].id, "monkey");
+            assert_eq!(unknown[0].rule.as_deref(), Some("root"));
+        },
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    let mut grammar: Grammar<i32> = Grammar::new();
+    grammar.rule("root", "monkey @Expected a gorilla.@ gorilla");
+    grammar.map("branch", "monkey", |_, _| Err(String::from("Monkey business.")));
+
+    let compiled = grammar.compile().unwrap();
+
+    match compiled.scan("banana", "monkey") {
+        Err(GrammarError::UnknownRoot { id }) => assert_eq!(id, "banana"),
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    match compiled.scan("root", "gorilla") {
+        Err(GrammarError::Parse { position, .. }) => assert_eq!(position.offset, 0),
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    match compiled.scan("root", "monkey chimp") {
+        Err(GrammarError::NoBacktrack { position, rule, msg }) => {
+            assert_eq!(position.offset, 7);
+            assert_eq!(rule, "root");
+            assert_eq!(msg, "Expected a gorilla.");
+        },
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    match compiled.scan("branch", "monkey") {
+        Err(GrammarError::Branch { rule, msg }) => {
+            assert_eq!(rule, "branch");
+            assert_eq!(msg, "Monkey business.");
+        },
+        other => panic!("Unexpected {:?}", other),
+    }
+}
@@ -0,0 +1,50 @@
+use grammar::{Expected, Grammar};
+
+#[test]
+fn error_position() {
+    let mut grammar: Grammar<i32> = Grammar::new();
+    grammar.rule("digit", "[0-9]");
+    grammar.rule("num", "<digit>+");
+    grammar.rule("add", "<num>(\\+<num>)*");
+    grammar.rule("lines", "<add>(\n<add>)*");
+
+    let compiled = grammar.compile().unwrap();
+
+    let err = compiled.scan("lines", "1+2\n3+x").unwrap_err();
+    let pos = err.position().unwrap();
+    assert_eq!(pos.offset, 6);
+    assert_eq!(pos.line, 2);
+    assert_eq!(pos.column, 3);
+    assert_eq!(err.rule(), Some("digit"));
+    assert_eq!(err.expected(), &[
+        Expected::CharRange { start: '0', end: '9' }, 
+        Expected::Rule(String::from("digit")), 
+        Expected::Rule(String::from("num")),
+    ]);
+    assert_eq!(format!("{}", err), "Parse error at line 2, column 3 in rule \"digit\", expected [0-9], <digit> or <num>.");
+
+    let err = compiled.scan("lines", "1+2💝").unwrap_err();
+    let pos = err.position().unwrap();
+    assert_eq!(pos.offset, 3);
+    assert_eq!(pos.column, 4);
+    assert_eq!(err.expected(), &[
+        Expected::CharRange { start: '0', end: '9' }, 
+        Expected::Eof,
+        Expected::Literal(String::from("\n")),
+        Expected::Literal(String::from("+")),
+        Expected::Rule(String::from("digit")), 
+    ]);
+}
+
+#[test]
+fn error_position_branch_fn() {
+    let mut grammar: Grammar<i32> = Grammar::new();
+    grammar.map("root", "monkey", |_, _| Err(String::from("No monkeys allowed.")));
+
+    let compiled = grammar.compile().unwrap();
+
+    let err = compiled.scan("root", "monkey").unwrap_err();
+    assert!(err.position().is_none());
+    assert_eq!(err.rule(), Some("root"));
+    assert_eq!(format!("{}", err), "No monkeys allowed.");
+}
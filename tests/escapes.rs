@@ -0,0 +1,37 @@
+use grammar::{Grammar, GrammarError};
+
+#[test]
+fn escapes() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("tabbed", "[a-z]+\\t[a-z]+\\r?\\n", |_, l| Ok(l.to_string()));
+    grammar.map("smiley", "[\\u{1F600}-\\u{1F64F}]\\u{2764}", |_, l| Ok(l.to_string()));
+    grammar.map("del", "[^\\x7F\\n]+\\x7F", |_, l| Ok(l.to_string()));
+    grammar.map("path", "c:\\\\[a-z]+\\\\\\\\n", |_, l| Ok(l.to_string()));
+    grammar.map("nul", "(~\\0,\\u{2400})", |_, l| Ok(l.to_string()));
+
+    let compiled_grammar = grammar.compile().unwrap();
+
+    assert_eq!(compiled_grammar.scan("tabbed", "key\tvalue\r\n").unwrap(), vec!["key\tvalue\r\n"]);
+    assert!(compiled_grammar.scan("tabbed", "key value\n").is_err());
+    assert_eq!(compiled_grammar.scan("smiley", "😀❤").unwrap(), vec!["😀❤"]);
+    assert!(compiled_grammar.scan("smiley", "☺❤").is_err());
+    assert_eq!(compiled_grammar.scan("del", "ab\x7F").unwrap(), vec!["ab\x7F"]);
+    assert!(compiled_grammar.scan("del", "a\nb\x7F").is_err());
+    assert_eq!(compiled_grammar.scan("path", "c:\\dir\\\\n").unwrap(), vec!["c:\\dir\\\\n"]);
+    assert_eq!(compiled_grammar.scan("nul", "\0").unwrap(), vec!["␀"]);
+}
+
+#[test]
+fn escapes_errors() {
+    let mut grammar: Grammar<String> = Grammar::new();
+
+    match grammar.try_rule("surrogate", "\\u{D800}") {
+        Err(GrammarError::Syntax { id, source }) => {
+            assert_eq!(id, "surrogate");
+            assert_eq!(source.to_string(), "Invalid char escape \"\\u{D800}\".");
+        },
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    assert!(grammar.try_rule("too-big", "\\u{110000}").is_err());
+}
@@ -0,0 +1,62 @@
+use grammar::{Grammar, GrammarError, Span};
+
+fn grammar() -> Grammar<u32> {
+    let mut grammar: Grammar<u32> = Grammar::new();
+    grammar.map("octet", "[0-9]{1,3}", |_, l| match l.parse::<u32>() {
+        Ok(x) if x <= 255 => Ok(x),
+        _ => Err(format!("Octet {} out of range.", l)),
+    });
+    grammar.map("ip", "<octet>.<octet>.<octet>.<octet>", |b, _| Ok(b.iter().fold(0, |acc, x| acc * 256 + x)));
+    grammar.rule("digits", "[0-9]*");
+    grammar
+}
+
+#[test]
+fn find() {
+    let compiled_grammar = grammar().compile().unwrap();
+    let log = "GET / from 10.0.0.1 at 12:00, ok\nPOST /ä from 192.168.1.20";
+
+    let m = compiled_grammar.find("ip", log).unwrap().unwrap();
+    assert_eq!(m.span, Span { start: 11, end: 19 });
+    assert_eq!(m.text, "10.0.0.1");
+    assert_eq!(m.values, vec![167772161]);
+
+    let matches: Vec<_> = compiled_grammar.find_iter("ip", log).map(|x| x.unwrap()).collect();
+    assert_eq!(matches.iter().map(|x| x.text).collect::<Vec<_>>(), vec!["10.0.0.1", "192.168.1.20"]);
+    assert_eq!(matches[1].span.start, 47);
+
+    assert!(compiled_grammar.is_match("ip", log).unwrap());
+    assert!(!compiled_grammar.is_match("ip", "no addresses here, 1.2.3").unwrap());
+    assert!(compiled_grammar.find("ip", "").unwrap().is_none());
+    assert!(matches!(compiled_grammar.find("nope", log), Err(GrammarError::UnknownRoot { .. })));
+}
+
+#[test]
+fn find_empty() {
+    let compiled_grammar = grammar().compile().unwrap();
+
+    let spans: Vec<_> = compiled_grammar.find_iter("digits", "a12ä3").map(|x| {
+        let x = x.unwrap();
+        (x.span.start, x.span.end)
+    }).collect();
+
+    assert_eq!(spans, vec![(0, 0), (1, 3), (5, 6)]);
+}
+
+#[test]
+fn find_error() {
+    let compiled_grammar = grammar().compile().unwrap();
+    let mut matches = compiled_grammar.find_iter("ip", "1.2.3.4 and 1.2.3.999");
+
+    assert_eq!(matches.next().unwrap().unwrap().text, "1.2.3.4");
+
+    match matches.next() {
+        Some(Err(GrammarError::Branch { rule, msg })) => {
+            assert_eq!(rule, "octet");
+            assert_eq!(msg, "Octet 999 out of range.");
+        },
+        other => panic!("Unexpected {:?}", other.map(|x| x.map(|x| x.text))),
+    }
+
+    assert!(matches.next().is_none());
+}
@@ -0,0 +1,60 @@
+use grammar::Grammar;
+
+#[test]
+fn ignore_case() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("name", "[a-z]+", |_, l| Ok(l.to_string()));
+    grammar.map("select", "(?i:select) <name> (?i:from) <name>", |b, _| Ok(b.join(",")));
+    grammar.map("flag", "(?i:(~yes,1|no,0)|[x-z]{2})", |_, l| Ok(l.to_string()));
+    grammar.map("greek", "(?i:σοφία)", |_, l| Ok(l.to_string()));
+
+    let compiled_grammar = grammar.compile().unwrap();
+
+    assert_eq!(compiled_grammar.scan("select", "SELECT a FROM b").unwrap(), vec!["a,b"]);
+    assert_eq!(compiled_grammar.scan("select", "sElEcT a fRoM b").unwrap(), vec!["a,b"]);
+    assert!(compiled_grammar.scan("select", "select A from b").is_err());
+    assert_eq!(compiled_grammar.scan("flag", "YES").unwrap(), vec!["1"]);
+    assert_eq!(compiled_grammar.scan("flag", "No").unwrap(), vec!["0"]);
+    assert_eq!(compiled_grammar.scan("flag", "Xz").unwrap(), vec!["Xz"]);
+    assert_eq!(compiled_grammar.scan("greek", "ΣΟΦΊΑ").unwrap(), vec!["ΣΟΦΊΑ"]);
+    assert!(compiled_grammar.scan("greek", "ΣΟΦΙΑ").is_err());
+}
+
+#[test]
+fn rule_case_insensitive() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.rule("name", "[a-z]+");
+    grammar.rule_case_insensitive("keyword", "(begin|end)_<name>");
+
+    let compiled_grammar = grammar.compile().unwrap();
+
+    assert!(compiled_grammar.scan("keyword", "BEGIN x").is_ok());
+    assert!(compiled_grammar.scan("keyword", "End  x").is_ok());
+    assert!(compiled_grammar.scan("keyword", "end X").is_err());
+    assert!(compiled_grammar.scan("keyword", "ſtart x").is_err());
+
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.rule_case_insensitive("s", "[a-s]tr\\u{212A}");
+
+    let compiled_grammar = grammar.compile().unwrap();
+
+    assert!(compiled_grammar.scan("s", "ſtrk").is_ok());
+    assert!(compiled_grammar.scan("s", "STRK").is_ok());
+    assert!(Grammar::<String>::new().try_rule("x", "(?i:oops").is_err());
+}
+
+#[test]
+fn ignore_case_any_char_except() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("not-x", "(?i:[^x])", |_, l| Ok(l.to_string()));
+    grammar.rule_case_insensitive("neg", "[^xk]+");
+
+    let compiled_grammar = grammar.compile().unwrap();
+
+    assert_eq!(compiled_grammar.scan("not-x", "y").unwrap(), vec!["y"]);
+    assert!(compiled_grammar.scan("not-x", "x").is_err());
+    assert!(compiled_grammar.scan("not-x", "X").is_err());
+    assert!(compiled_grammar.scan("neg", "abc").is_ok());
+    assert!(compiled_grammar.scan("neg", "aXb").is_err());
+    assert!(compiled_grammar.scan("neg", "a\u{212A}").is_err());
+}
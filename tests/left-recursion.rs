@@ -0,0 +1,64 @@
+use grammar::{Grammar, GrammarError};
+
+fn cycles(grammar: Grammar<i32>) -> Vec<Vec<String>> {
+    match grammar.compile().err() {
+        Some(GrammarError::LeftRecursion { cycles }) => cycles,
+        other => panic!("Unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn left_recursion() {
+    let mut grammar: Grammar<i32> = Grammar::new();
+    grammar.rule("num", "[0-9]+");
+    grammar.rule("expr", "<expr>\\+<num>");
+    assert_eq!(cycles(grammar), vec![vec!["expr", "expr"]]);
+
+    let mut grammar: Grammar<i32> = Grammar::new();
+    grammar.rule("num", "[0-9]+");
+    grammar.rule("expr", "( <expr>\\+<num>|<num>)");
+    assert_eq!(cycles(grammar), vec![vec!["expr", "expr"]]);
+
+    let mut grammar: Grammar<i32> = Grammar::new();
+    grammar.rule("num", "[0-9]+");
+    grammar.rule("add", " <mul>?(\\+<num>)*");
+    grammar.rule("mul", "(\\(|<add>)\\*<num>");
+    assert_eq!(cycles(grammar), vec![vec!["add", "mul", "add"]]);
+
+    let mut grammar: Grammar<i32> = Grammar::new_with_ws("<comment>");
+    grammar.rule("comment", "(_)?#[^\n]*");
+    assert_eq!(cycles(grammar), vec![vec!["comment", "comment"]]);
+
+    let mut grammar: Grammar<i32> = Grammar::new();
+    grammar.rule("monkey", "!<banana>monkey");
+    grammar.rule("banana", "(<monkey>|banana)");
+    assert_eq!(cycles(grammar), vec![vec!["banana", "monkey", "banana"]]);
+}
+
+#[test]
+fn no_left_recursion() {
+    let mut grammar: Grammar<i32> = Grammar::new();
+    grammar.rule("num", "[0-9]+");
+    grammar.rule("expr", "<num>(\\+<expr>)?");
+    grammar.rule("group", "\\( <group>? \\)");
+    assert!(grammar.compile().is_ok());
+}
+
+#[test]
+fn left_recursion_grow() {
+    let mut grammar: Grammar<i32> = Grammar::new();
+    grammar.map("num", "[0-9]+", |_, l| Ok(l.parse().unwrap()));
+    grammar.map("sub", "(<sub> - <num>|<num>)", |b, _| Ok(if b.len() == 1 { b[0] } else { b[0] - b[1] }));
+    grammar.map("div", "(<div>/<num>|<num>)", |b, _| Ok(if b.len() == 1 { b[0] } else { b[0] / b[1] }));
+    grammar.rule("items", "(<items>,<num>|<num>)");
+    grammar.map("text", "(<text>,<num>|<num>)", |_, l| Ok(l.len() as i32));
+
+    let compiled = grammar.compile().unwrap();
+
+    assert_eq!(compiled.scan("sub", "7").unwrap(), vec![7]);
+    assert_eq!(compiled.scan("sub", "10 - 3 - 2").unwrap(), vec![5]);
+    assert!(compiled.scan("sub", "10 - ").is_err());
+    assert_eq!(compiled.scan("div", "100/5/2").unwrap(), vec![10]);
+    assert_eq!(compiled.scan("items", "1,2,3").unwrap(), vec![1, 2, 3]);
+    assert_eq!(compiled.scan("text", "1,22,333").unwrap(), vec![8]);
+}
@@ -0,0 +1,60 @@
+use grammar::{Assoc, Grammar, Node, Operators};
+
+fn find<'a>(node: &'a Node, text: &str) -> &'a Node {
+    if node.text == text {
+        return node;
+    }
+
+    node.children.iter().map(|x| find(x, text)).find(|x| x.text == text).unwrap_or(node)
+}
+
+#[test]
+fn lossless_tree() {
+    let mut grammar: Grammar<i32> = Grammar::new_with_ws("(\\ |\t|\n|<comment>)");
+    grammar.rule("comment", "#[^\n]*");
+    grammar.rule("num", "[0-9]+");
+    grammar.rule("name", "[a-z]+");
+    grammar.rule("let", "let_<name> = <num> ;");
+    grammar.rule("program", " (<let> )*");
+
+    let compiled_grammar = grammar.compile().unwrap();
+    let code = "  # answer\nlet x=42;  # trailing\n\n# about y\n  let   y = 7 ;\n";
+    let tree = compiled_grammar.parse_tree("program", code).unwrap();
+
+    assert_eq!(tree.to_string(), code);
+    assert_eq!(tree.children.len(), 2);
+
+    let first = &tree.children[0];
+    assert_eq!(first.text, "let x=42;");
+    assert_eq!(first.leading, "  # answer\n");
+    assert_eq!(first.trailing, "  # trailing\n");
+
+    let second = &tree.children[1];
+    assert_eq!(second.text, "let   y = 7 ;");
+    assert_eq!(second.leading, "\n# about y\n  ");
+    assert_eq!(second.trailing, "\n");
+    assert!(second.children.iter().all(|x| x.rule != "comment"));
+
+    let y = find(second, "y");
+    assert_eq!(y.leading, "   ");
+    assert_eq!(y.trailing, " ");
+    assert_eq!(y.to_string(), "   y ");
+}
+
+#[test]
+fn lossless_tree_operators() {
+    let mut grammar: Grammar<i32> = Grammar::new();
+    grammar.rule("num", "[0-9]+");
+    grammar.rule("group", "\\( <expr> \\)");
+    grammar.rule("atom", "(<num>|<group>)");
+    grammar.operators("expr", "atom", Operators::<i32>::new()
+        .infix(" \\+ ", 1, Assoc::Left, |a, b, _| Ok(a + b))
+        .prefix("- ", 2, |a, _| Ok(-a)));
+    grammar.rule("root", " <expr> ");
+
+    let compiled_grammar = grammar.compile().unwrap();
+
+    for code in &["1", " 1 + 2 ", "1+\n2 +( - 3 )\n", "\t- ( 1 )  +2"] {
+        assert_eq!(compiled_grammar.parse_tree("root", code).unwrap().to_string(), *code);
+    }
+}
@@ -0,0 +1,49 @@
+use grammar::Grammar;
+
+#[test]
+fn map_named() {
+    let mut grammar: Grammar<i64> = Grammar::new();
+    grammar.map("num", "[0-9]+", |_, l| l.parse().map_err(|_| String::from("Not a number.")));
+    grammar.map_named("add", "<lhs:num> \\+ <rhs:num>", |c, _| Ok(c.get("lhs").unwrap() + c.get("rhs").unwrap()));
+    grammar.map_named("assign", "x( = <value:num>)? ;", |c, _| Ok(*c.get("value").unwrap_or(&-1)));
+    grammar.map_named("call", "f\\( (<args:num>( , <args:num>)*)? \\)", |c, _| Ok(c.get_all("args").into_iter().sum()));
+    grammar.map_named("sub", "(<lhs:sub> - <rhs:num>|<rhs:num>)", |c, _| {
+        match c.get("lhs") {
+            Some(lhs) => Ok(lhs - c.get("rhs").unwrap()),
+            None => Ok(*c.get("rhs").unwrap()),
+        }
+    });
+
+    let mut compiled_grammar = grammar.compile().unwrap();
+
+    assert_eq!(compiled_grammar.scan("add", "1 + 2").unwrap(), vec![3]);
+    assert_eq!(compiled_grammar.scan("assign", "x = 5;").unwrap(), vec![5]);
+    assert_eq!(compiled_grammar.scan("assign", "x;").unwrap(), vec![-1]);
+    assert_eq!(compiled_grammar.scan("call", "f(1, 2, 3)").unwrap(), vec![6]);
+    assert_eq!(compiled_grammar.scan("call", "f()").unwrap(), vec![0]);
+    assert_eq!(compiled_grammar.scan("sub", "10 - 3 - 2").unwrap(), vec![5]);
+
+    compiled_grammar.memoize(true);
+    assert_eq!(compiled_grammar.scan("call", "f(1, 2, 3)").unwrap(), vec![6]);
+    assert_eq!(compiled_grammar.scan("sub", "10 - 3 - 2").unwrap(), vec![5]);
+}
+
+#[test]
+fn map_named_text() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("word", "[a-z]+", |_, l| Ok(l.to_uppercase()));
+    grammar.rule("words", "<word>( <word>)*");
+    grammar.map_named("pair", "<key:word>=<value:words>", |c, _| Ok(format!("{}:{}", c.text("key").unwrap(), c.get_all("value").len())));
+    grammar.map_named("pairs", "<first:pair>(;<more:pair>)*", |mut c, _| {
+        assert!(!c.contains("key"));
+        assert_eq!(c.texts("more"), vec!["b=x y", "c=z"]);
+
+        let mut pairs = vec![c.take("first").unwrap()];
+        pairs.extend(c.take_all("more"));
+        assert!(c.get("first").is_none());
+        Ok(pairs.join(","))
+    });
+
+    let compiled_grammar = grammar.compile().unwrap();
+    assert_eq!(compiled_grammar.scan("pairs", "a=x;b=x y;c=z").unwrap(), vec!["a:1,b:2,c:1"]);
+}
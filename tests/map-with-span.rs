@@ -0,0 +1,45 @@
+use grammar::{Assoc, Grammar, Operators, Position, Span};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Ast {
+    Num(Span),
+    Call(Span, Vec<Span>),
+}
+
+#[test]
+fn map_with_span() {
+    let mut grammar: Grammar<Ast> = Grammar::new();
+    grammar.map_with_span("num", "[0-9]+", |_, _, span| Ok(Ast::Num(span)));
+    grammar.map_with_span("call", "[a-z]+\\( <num>( , <num>)* \\)", |b, _, span| Ok(Ast::Call(span, b.into_iter().map(|x| x.1).collect())));
+    grammar.rule("root", " <call> ");
+
+    let compiled_grammar = grammar.compile().unwrap();
+    let code = "\n  max(1, 22 ,333)";
+
+    match &compiled_grammar.scan("root", code).unwrap()[0] {
+        Ast::Call(span, args) => {
+            assert_eq!(*span, Span { start: 3, end: 18 });
+            assert_eq!(*args, vec![Span { start: 7, end: 8 }, Span { start: 10, end: 12 }, Span { start: 14, end: 17 }]);
+            assert_eq!(&code[args[2].start..args[2].end], "333");
+
+            let (start, end) = span.locate(code);
+            assert_eq!(start, Position { offset: 3, line: 2, column: 3 });
+            assert_eq!(end, Position { offset: 18, line: 2, column: 18 });
+        },
+        other => panic!("Unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn map_with_span_nested() {
+    let mut grammar: Grammar<Span> = Grammar::new();
+    grammar.map_with_span("num", "[0-9]+", |_, _, span| Ok(span));
+    grammar.operators("expr", "num", Operators::new().infix(" \\+ ", 1, Assoc::Left, |a: Span, b: Span, _| Ok(Span { start: a.start, end: b.end })));
+    grammar.map_with_span("list", "(<expr>,)*<expr>", |b, _, span| {
+        assert!(b.iter().all(|(x, y)| x == y));
+        Ok(span)
+    });
+
+    let compiled_grammar = grammar.compile().unwrap();
+    assert_eq!(compiled_grammar.scan("list", "1 + 2,34").unwrap(), vec![Span { start: 0, end: 8 }]);
+}
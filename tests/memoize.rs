@@ -0,0 +1,76 @@
+use grammar::{Grammar, ScanStats};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+fn grammar() -> Grammar<String> {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("num", "[0-9]+", |_, l| Ok(l.to_string()));
+    grammar.map("group", "\\(<expr>\\)", |b, _| Ok(format!("({})", b[0])));
+    grammar.rule("atom", "(<num>|<group>)");
+    grammar.map("add", "<atom>\\+<expr>", |b, _| Ok(format!("{}+{}", b[0], b[1])));
+    grammar.map("sub", "<atom>-<expr>", |b, _| Ok(format!("{}-{}", b[0], b[1])));
+    grammar.rule("expr", "(<add>|<sub>|<atom>)");
+    grammar
+}
+
+#[test]
+fn memoize() {
+    let mut compiled_grammar = grammar().compile().unwrap();
+    let code = "((((((((1-2))))))))-3";
+
+    let (plain, plain_stats) = compiled_grammar.scan_with_stats("expr", code);
+    assert_eq!(plain.unwrap(), vec![code]);
+    assert_eq!(plain_stats, ScanStats::default());
+
+    compiled_grammar.memoize(true);
+    let (memo, memo_stats) = compiled_grammar.scan_with_stats("expr", code);
+    assert_eq!(memo.unwrap(), vec![code]);
+    assert!(memo_stats.memo_hits > 0);
+    assert!(memo_stats.memo_misses < 100);
+
+    compiled_grammar.memoize(false);
+    assert_eq!(compiled_grammar.scan_with_stats("expr", code).1, ScanStats::default());
+}
+
+#[test]
+fn memoize_errors() {
+    let plain_grammar = grammar().compile().unwrap();
+    let mut memo_grammar = grammar().compile().unwrap();
+    memo_grammar.memoize(true);
+
+    for code in &["", "1+", "(1-2", "((1)+(2)-)", "1-2x", "!(1)"] {
+        let plain = plain_grammar.scan("expr", code).unwrap_err();
+        let memo = memo_grammar.scan("expr", code).unwrap_err();
+        assert_eq!(plain.to_string(), memo.to_string());
+    }
+}
+
+static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, PartialEq)]
+struct Counted(usize);
+
+impl Clone for Counted {
+    fn clone(&self) -> Self {
+        CLONES.fetch_add(1, Ordering::SeqCst);
+        Counted(self.0)
+    }
+}
+
+#[test]
+fn memoize_clones() {
+    let mut grammar: Grammar<Counted> = Grammar::new();
+    grammar.map("item", "[0-9]+", |_, l| Ok(Counted(l.parse().unwrap())));
+    grammar.rule("list", "<item>(,<list>)?");
+    grammar.rule("top", "(<list>;|<list>\\.)");
+
+    let mut compiled_grammar = grammar.compile().unwrap();
+    compiled_grammar.memoize(true);
+
+    let items = 100;
+    let code = format!("{}.", (0..items).map(|x| x.to_string()).collect::<Vec<_>>().join(","));
+    let (result, stats) = compiled_grammar.scan_with_stats("top", &code);
+
+    assert_eq!(result.unwrap(), (0..items).map(Counted).collect::<Vec<_>>());
+    assert!(stats.memo_hits > 0);
+    assert!(CLONES.load(Ordering::SeqCst) < 4 * items);
+}
@@ -0,0 +1,129 @@
+use grammar::{Assoc, Grammar, GrammarError, Operators};
+
+fn atoms() -> Grammar<String> {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("num", "[0-9]+", |_, l| Ok(l.to_string()));
+    grammar.map("group", "\\(<expr>\\)", |b, _| Ok(b[0].clone()));
+    grammar.rule("atom", "(<num>|<group>)");
+    grammar
+}
+
+fn binary() -> impl Fn(String, String, &str) -> Result<String, String> {
+    |a, b, op| Ok(format!("({} {} {})", a, op.trim(), b))
+}
+
+#[test]
+fn operators() {
+    let mut grammar = atoms();
+    grammar.operators("expr", "atom", Operators::new()
+        .infix(" \\+ ", 1, Assoc::Left, binary())
+        .infix(" - ", 1, Assoc::Left, binary())
+        .infix(" \\*\\* ", 3, Assoc::Right, binary())
+        .infix(" \\* ", 2, Assoc::Left, binary())
+        .infix(" \\< ", 0, Assoc::None, binary())
+        .prefix("-", 4, |a, _| Ok(format!("-{}", a)))
+        .postfix("\\!", 5, |a, _| Ok(format!("{}!", a))));
+
+    let compiled_grammar = grammar.compile().unwrap();
+    let scan = |code: &str| compiled_grammar.scan("expr", code).map(|b| b[0].clone());
+
+    assert_eq!(scan("1").unwrap(), "1");
+    assert_eq!(scan("1 + 2 - 3").unwrap(), "((1 + 2) - 3)");
+    assert_eq!(scan("1 + 2 * 3").unwrap(), "(1 + (2 * 3))");
+    assert_eq!(scan("2 ** 3 ** 2").unwrap(), "(2 ** (3 ** 2))");
+    assert_eq!(scan("2 * 3 ** 2").unwrap(), "(2 * (3 ** 2))");
+    assert_eq!(scan("-2 ** 2").unwrap(), "(-2 ** 2)");
+    assert_eq!(scan("2 * -3!").unwrap(), "(2 * -3!)");
+    assert_eq!(scan("(1 + 2) * 3").unwrap(), "((1 + 2) * 3)");
+    assert_eq!(scan("1 < 2 + 3").unwrap(), "(1 < (2 + 3))");
+    assert!(scan("1 < 2 < 3").is_err());
+    assert!(scan("1 +").is_err());
+}
+
+#[test]
+fn operators_highest_precedence() {
+    let mut grammar = atoms();
+    grammar.operators("expr", "atom", Operators::new()
+        .infix(" \\+ ", u32::MAX, Assoc::Left, binary())
+        .infix(" \\< ", u32::MAX - 1, Assoc::None, binary())
+        .infix(" = ", u32::MAX, Assoc::None, binary()));
+
+    let compiled_grammar = grammar.compile().unwrap();
+    let scan = |code: &str| compiled_grammar.scan("expr", code).map(|b| b[0].clone());
+
+    assert_eq!(scan("1 + 2 + 3").unwrap(), "((1 + 2) + 3)");
+    assert_eq!(scan("1 < 2 + 3").unwrap(), "(1 < (2 + 3))");
+    assert!(scan("1 = 2 = 3").is_err());
+}
+
+#[test]
+fn operators_empty() {
+    let mut grammar = atoms();
+    grammar.operators("expr", "atom", Operators::new().infix("\\+", 1, Assoc::Left, binary()).postfix(" ", 2, |a, _| Ok(a)));
+
+    match grammar.compile().err() {
+        Some(GrammarError::EmptyOperator { rule, expr }) => assert_eq!((rule.as_str(), expr.as_str()), ("expr", " ")),
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    let mut grammar = atoms();
+    grammar.operators("expr", "atom", Operators::new().prefix("-?", 1, |a, _| Ok(format!("-{}", a))));
+
+    match grammar.compile().err() {
+        Some(err @ GrammarError::EmptyOperator { .. }) => assert_eq!(err.to_string(), "Operator \"-?\" of rule \"expr\" can match empty input."),
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    let mut grammar = atoms();
+    grammar.rule("sign", "(\\+|-)?");
+    grammar.operators("expr", "atom", Operators::new().infix("<sign>", 1, Assoc::Left, binary()));
+    assert!(matches!(grammar.compile().err(), Some(GrammarError::EmptyOperator { .. })));
+}
+
+#[test]
+fn operators_in_rule() {
+    let mut grammar = atoms();
+    grammar.operators("expr", "atom", Operators::new().infix("\\+", 1, Assoc::Left, binary()));
+    grammar.map("list", "<expr>(,<expr>)*", |b, _| Ok(b.join(" ")));
+
+    let compiled_grammar = grammar.compile().unwrap();
+    assert_eq!(compiled_grammar.scan("list", "1+2,3,(4+5)+6").unwrap(), vec!["(1 + 2) 3 ((4 + 5) + 6)"]);
+}
+
+#[test]
+fn operators_errors() {
+    let mut grammar = atoms();
+    grammar.operators("expr", "atom", Operators::new()
+        .infix("/", 1, Assoc::Left, |a, b, _| if b == "0" { Err(String::from("Division by zero.")) } else { Ok(format!("{}/{}", a, b)) }));
+
+    let compiled_grammar = grammar.compile().unwrap();
+
+    match compiled_grammar.scan("expr", "1/0") {
+        Err(GrammarError::Branch { rule, msg }) => {
+            assert_eq!(rule, "expr");
+            assert_eq!(msg, "Division by zero.");
+        },
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    let mut grammar = atoms();
+    grammar.operators("expr", "atom", Operators::new().infix("\\+<op>", 1, Assoc::Left, binary()));
+
+    match grammar.compile().err() {
+        Some(GrammarError::UnknownRules(unknown)) => assert_eq!(unknown[0].id, "op"),
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    let mut grammar = atoms();
+    assert!(grammar.try_operators("expr", "atom", Operators::new().infix("(", 1, Assoc::Left, binary())).is_err());
+    assert!(grammar.try_operators("num", "atom", Operators::new()).is_err());
+
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.rule("num", "[0-9]+");
+    grammar.operators("expr", "num", Operators::new().infix("\\+", 1, Assoc::Left, binary()));
+
+    match grammar.compile().unwrap().scan("expr", "1+2") {
+        Err(GrammarError::Branch { rule, .. }) => assert_eq!(rule, "expr"),
+        other => panic!("Unexpected {:?}", other),
+    }
+}
@@ -0,0 +1,62 @@
+use grammar::{Assoc, Grammar, GrammarError, Node, Operators, Span};
+
+// Writes a tree as `rule(child child)`, or `rule"text"` for a leaf.
+fn show(node: &Node) -> String {
+    if node.children.is_empty() {
+        format!("{}{:?}", node.rule, node.text)
+    }
+    else {
+        format!("{}({})", node.rule, node.children.iter().map(show).collect::<Vec<_>>().join(" "))
+    }
+}
+
+#[test]
+fn parse_tree() {
+    let mut grammar: Grammar<i32> = Grammar::new();
+    grammar.rule("digit", "[0-9]");
+    grammar.map("num", "<digit>+", |_, _| Err(String::from("Never called.")));
+    grammar.rule("name", "[a-z]+");
+    grammar.rule("arg", "(<num>|<name>)");
+    grammar.rule("call", "<name>\\( <arg>( , <arg>)* \\)");
+
+    let compiled_grammar = grammar.compile().unwrap();
+    let tree = compiled_grammar.parse_tree("call", "max(12, x)").unwrap();
+
+    assert_eq!(show(&tree), "call(name\"max\" arg(num(digit\"1\" digit\"2\")) arg(name\"x\"))");
+    assert_eq!(tree.span, Span { start: 0, end: 10 });
+    assert_eq!(tree.text, "max(12, x)");
+    assert_eq!(tree.children[1].span, Span { start: 4, end: 6 });
+    assert_eq!(tree.children[2].text, "x");
+
+    match compiled_grammar.parse_tree("call", "max(12,)") {
+        Err(GrammarError::Parse { position, .. }) => assert_eq!(position.offset, 7),
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    assert!(compiled_grammar.scan("call", "max(12, x)").is_err());
+}
+
+#[test]
+fn parse_tree_operators() {
+    let mut grammar: Grammar<i32> = Grammar::new();
+    grammar.rule("num", "[0-9]+");
+    grammar.rule("group", "\\(<expr>\\)");
+    grammar.rule("atom", "(<num>|<group>)");
+    grammar.operators("expr", "atom", Operators::<i32>::new()
+        .infix("\\+", 1, Assoc::Left, |a, b, _| Ok(a + b))
+        .infix("\\*", 2, Assoc::Left, |a, b, _| Ok(a * b))
+        .prefix("-", 3, |a, _| Ok(-a)));
+    grammar.rule("sum", "(<sum>;<expr>|<expr>)");
+
+    let compiled_grammar = grammar.compile().unwrap();
+
+    let tree = compiled_grammar.parse_tree("expr", "1+2*-3").unwrap();
+    assert_eq!(show(&tree), "expr(atom(num\"1\") expr(atom(num\"2\") expr(atom(num\"3\"))))");
+    assert_eq!(tree.children[1].text, "2*-3");
+
+    let tree = compiled_grammar.parse_tree("expr", "(1)").unwrap();
+    assert_eq!(show(&tree), "expr(atom(group(expr(atom(num\"1\")))))");
+
+    let tree = compiled_grammar.parse_tree("sum", "1;2;3").unwrap();
+    assert_eq!(show(&tree), "sum(sum(sum(expr(atom(num\"1\"))) expr(atom(num\"2\"))) expr(atom(num\"3\")))");
+}
@@ -0,0 +1,98 @@
+use grammar::{Grammar, GrammarError};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Stmt {
+    Let(String, i32),
+    Error(String),
+}
+
+fn grammar() -> Grammar<Stmt> {
+    let mut grammar: Grammar<Stmt> = Grammar::new();
+    grammar.map("let", "let_[a-z]+ = [0-9]+ ;", |_, l| {
+        let (name, value) = l[4..l.len() - 1].split_once('=').unwrap();
+        Ok(Stmt::Let(name.trim().to_string(), value.trim().parse().unwrap()))
+    });
+    grammar.rule("program", " (<let> )*");
+    grammar.recover("let", "[^;]*;", |l| Stmt::Error(l.to_string()));
+    grammar
+}
+
+#[test]
+fn recover() {
+    let compiled_grammar = grammar().compile().unwrap();
+    let (stmts, diagnostics) = compiled_grammar.scan_recover("program", "let a = 1;\nlet b = ;\nlet = 3;\nlet d = 4;\n");
+
+    assert_eq!(stmts, vec![
+        Stmt::Let(String::from("a"), 1),
+        Stmt::Error(String::from("let b = ;")),
+        Stmt::Error(String::from("let = 3;")),
+        Stmt::Let(String::from("d"), 4),
+    ]);
+
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].position().map(|x| (x.line, x.column)), Some((2, 9)));
+    assert_eq!(diagnostics[1].position().map(|x| (x.line, x.column)), Some((3, 5)));
+    assert_eq!(diagnostics[1].to_string(), "Parse error at line 3, column 5 in rule \"let\", expected [a-z] or whitespace.");
+
+    let (stmts, diagnostics) = compiled_grammar.scan_recover("program", "let a = 1; let b = 2");
+    assert_eq!(stmts, vec![Stmt::Let(String::from("a"), 1), Stmt::Error(String::from("let b = 2"))]);
+    assert_eq!(diagnostics.len(), 1);
+
+    let (stmts, diagnostics) = compiled_grammar.scan_recover("program", "let a = 1;");
+    assert_eq!(stmts, vec![Stmt::Let(String::from("a"), 1)]);
+    assert!(diagnostics.is_empty());
+
+    assert!(compiled_grammar.scan("program", "let a = 1; let b = ;").is_err());
+}
+
+#[test]
+fn recover_tree() {
+    let compiled_grammar = grammar().compile().unwrap();
+    let code = "let a = 1;\n  let b = ;\nlet c = 3;";
+    let (tree, diagnostics) = compiled_grammar.parse_tree_recover("program", code);
+    let tree = tree.unwrap();
+
+    assert_eq!(tree.to_string(), code);
+    assert_eq!(tree.children.iter().map(|x| x.error).collect::<Vec<_>>(), vec![false, true, false]);
+    assert_eq!(tree.children[1].text, "let b = ;");
+    assert_eq!(tree.children[1].leading, "  ");
+    assert_eq!(diagnostics.len(), 1);
+
+    let (tree, diagnostics) = compiled_grammar.parse_tree_recover("none", code);
+    assert!(tree.is_none());
+    assert!(matches!(diagnostics[..], [GrammarError::UnknownRoot { .. }]));
+}
+
+#[test]
+fn recover_errors() {
+    let mut grammar = grammar();
+    let err = grammar.try_recover("nope", ";", |l| Stmt::Error(l.to_string())).unwrap_err();
+    assert!(matches!(err, GrammarError::UnknownRecovery { ref id } if id == "nope"));
+    assert_eq!(err.to_string(), "Rule \"nope\" to recover not found.");
+    assert!(matches!(grammar.try_recover("let", "(;", |l| Stmt::Error(l.to_string())), Err(GrammarError::Syntax { .. })));
+
+    grammar.recover("program", "<sync>", |l| Stmt::Error(l.to_string()));
+    assert!(matches!(grammar.compile().err(), Some(GrammarError::UnknownRules(_))));
+}
+
+#[test]
+fn recover_valid() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("stmt", "[a-z]+;", |_, l| Ok(l.to_string()));
+    grammar.map("block", "\\{ (<stmt> )*\\}", |b, _| Ok(b.join(",")));
+    grammar.map("choice", "(<stmt>|[0-9]+)", |b, l| Ok(b.first().cloned().unwrap_or_else(|| l.to_string())));
+    grammar.recover("stmt", ";", |l| format!("error({})", l));
+
+    let compiled_grammar = grammar.compile().unwrap();
+
+    for (root, code) in [("block", "{ a; b; }"), ("block", "{ }"), ("choice", "a;"), ("choice", "12")] {
+        let (values, diagnostics) = compiled_grammar.scan_recover(root, code);
+        assert_eq!(values, compiled_grammar.scan(root, code).unwrap());
+        assert!(diagnostics.is_empty());
+    }
+
+    let (values, diagnostics) = compiled_grammar.scan_recover("block", "{ a; 1; b; }");
+    assert_eq!(values, vec!["a;,error(1;),b;"]);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].position().unwrap().offset, 5);
+}
@@ -0,0 +1,44 @@
+use grammar::{Grammar, GrammarError};
+
+#[test]
+fn replace_all() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("name", "[a-z]+", |_, l| Ok(l.to_string()));
+    grammar.map("call", "<name>\\( <name>( , <name>)* \\)", |b, _| Ok(b.join(",")));
+
+    let compiled_grammar = grammar.compile().unwrap();
+    let code = "let x = max(a, b) + min( c,d );";
+
+    let replaced = compiled_grammar.replace_all("call", code, |m| {
+        let names: Vec<&str> = m.values[0].split(',').collect();
+        format!("{}({})", names[0], names[1..].iter().rev().cloned().collect::<Vec<_>>().join(", "))
+    });
+
+    assert_eq!(replaced.unwrap(), "let x = max(b, a) + min(d, c);");
+
+    let mut spans = vec![];
+    let replaced = compiled_grammar.replace_all("name", "ab cd", |m| {
+        spans.push(m.span.start);
+        m.text.to_uppercase()
+    });
+
+    assert_eq!(replaced.unwrap(), "AB CD");
+    assert_eq!(spans, vec![0, 3]);
+    assert_eq!(compiled_grammar.replace_all("call", "1 + 2", |_| String::new()).unwrap(), "1 + 2");
+}
+
+#[test]
+fn replace_all_alter() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("quoted", "'((~\\',')|[^'])*'", |_, l| Ok(l.to_string()));
+
+    let compiled_grammar = grammar.compile().unwrap();
+    let replaced = compiled_grammar.replace_all("quoted", "a = 'it\\'s', b = 'x'", |m| format!("{:?}", &m.values[0][1..m.values[0].len() - 1]));
+    assert_eq!(replaced.unwrap(), "a = \"it's\", b = \"x\"");
+
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("num", "[0-9]+", |_, l| if l == "0" { Err(String::from("Zero.")) } else { Ok(l.to_string()) });
+
+    let compiled_grammar = grammar.compile().unwrap();
+    assert!(matches!(compiled_grammar.replace_all("num", "1 0", |m| m.text.to_string()), Err(GrammarError::Branch { .. })));
+}
@@ -0,0 +1,48 @@
+use grammar::{Grammar, GrammarError};
+
+#[test]
+fn scan_prefix() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("word", "[a-z0-9]+", |_, l| Ok(l.to_string()));
+    grammar.map("command", " <word>( <word>)* ;", |b, _| Ok(b.join(" ")));
+
+    let compiled_grammar = grammar.compile().unwrap();
+    let buffer = "set x 1; get x;\n  quit ; rest";
+
+    assert_eq!(compiled_grammar.scan_prefix("command", buffer).unwrap(), (vec![String::from("set x 1")], 8));
+    assert!(compiled_grammar.scan("command", buffer).is_err());
+
+    let mut commands = vec![];
+    let mut offset = 0;
+
+    while let Ok((values, consumed)) = compiled_grammar.scan_prefix_at("command", buffer, offset) {
+        commands.extend(values);
+        offset += consumed;
+    }
+
+    assert_eq!(commands, vec!["set x 1", "get x", "quit"]);
+    assert_eq!(&buffer[offset..], " rest");
+
+    match compiled_grammar.scan_prefix_at("command", buffer, offset) {
+        Err(GrammarError::Parse { position, .. }) => assert_eq!((position.offset, position.line, position.column), (29, 2, 14)),
+        other => panic!("Unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn scan_prefix_at_invalid_offset() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("w", "[a-zé]", |_, l| Ok(l.to_string()));
+
+    let compiled_grammar = grammar.compile().unwrap();
+
+    assert_eq!(compiled_grammar.scan_prefix_at("w", "éa", 2).unwrap(), (vec![String::from("a")], 1));
+    assert!(matches!(compiled_grammar.scan_prefix_at("w", "éa", 3), Err(GrammarError::Parse { .. })));
+
+    for offset in [1, 4, 10] {
+        match compiled_grammar.scan_prefix_at("w", "éa", offset) {
+            Err(GrammarError::InvalidOffset { offset: x }) => assert_eq!(x, offset),
+            other => panic!("Unexpected {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use grammar::{Grammar, GrammarError};
+use std::io::BufReader;
+
+fn records() -> Grammar<String> {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("field", "[^;\n]+", |_, l| Ok(l.to_string()));
+    grammar.map("record", "<field>(;<field>)*(\n|$)", |b, _| Ok(b.join("|")));
+    grammar
+}
+
+#[test]
+fn scan_reader() {
+    let compiled_grammar = records().compile().unwrap();
+    let input = "name;städte;end\nfoo;bär\nlast";
+
+    for capacity in 1..input.len() + 1 {
+        let reader = BufReader::with_capacity(capacity, input.as_bytes());
+        let records: Vec<_> = compiled_grammar.scan_reader("record", reader).map(|x| x.unwrap()).collect();
+        assert_eq!(records, vec![vec!["name|städte|end"], vec!["foo|bär"], vec!["last"]]);
+    }
+
+    assert_eq!(compiled_grammar.scan_reader("record", "".as_bytes()).count(), 0);
+}
+
+#[test]
+fn scan_reader_literal() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("word", "[a-z]+", |_, l| Ok(l.to_string()));
+    grammar.map("record", "begin <word> end ", |b, _| Ok(b[0].clone()));
+
+    let compiled_grammar = grammar.compile().unwrap();
+    let reader = BufReader::with_capacity(2, "begin x end begin yz end\n".as_bytes());
+    let records: Vec<_> = compiled_grammar.scan_reader("record", reader).map(|x| x.unwrap()).collect();
+    assert_eq!(records, vec![vec!["x"], vec!["yz"]]);
+}
+
+#[test]
+fn scan_reader_errors() {
+    let compiled_grammar = records().compile().unwrap();
+    let reader = BufReader::with_capacity(4, "a;b\nc;d\nef;;g\nh".as_bytes());
+    let mut records = compiled_grammar.scan_reader("record", reader);
+
+    assert_eq!(records.next().unwrap().unwrap(), vec!["a|b"]);
+    assert_eq!(records.next().unwrap().unwrap(), vec!["c|d"]);
+
+    match records.next() {
+        Some(Err(GrammarError::Parse { position, .. })) => assert_eq!((position.offset, position.line, position.column), (11, 3, 4)),
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    assert!(records.next().is_none());
+
+    let mut records = compiled_grammar.scan_reader("record", &b"a;\xFF\n"[..]);
+
+    match records.next() {
+        Some(Err(GrammarError::Io { source })) => assert_eq!(source.kind(), std::io::ErrorKind::InvalidData),
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    match compiled_grammar.scan_reader("nope", "a".as_bytes()).next() {
+        Some(Err(GrammarError::UnknownRoot { id })) => assert_eq!(id, "nope"),
+        other => panic!("Unexpected {:?}", other),
+    }
+}
@@ -0,0 +1,83 @@
+use grammar::{Grammar, GrammarError};
+use std::collections::HashMap;
+
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<String, usize>,
+}
+
+#[test]
+fn scan_with() {
+    let mut grammar: Grammar<usize, Interner> = Grammar::new();
+    grammar.map_with_context("name", "[a-z]+", |_, l, ctx: &mut Interner| {
+        let next = ctx.ids.len();
+        Ok(*ctx.ids.entry(l.to_string()).or_insert(next))
+    });
+    grammar.map("names", "<name>( <name>)*", |b, _| Ok(b.len()));
+
+    let compiled_grammar = grammar.compile().unwrap();
+
+    let mut first = Interner::default();
+    assert_eq!(compiled_grammar.scan_with("name", "foo", &mut first).unwrap(), vec![0]);
+    assert_eq!(compiled_grammar.scan_with("name", "bar", &mut first).unwrap(), vec![1]);
+    assert_eq!(compiled_grammar.scan_with("name", "foo", &mut first).unwrap(), vec![0]);
+
+    let mut second = Interner::default();
+    assert_eq!(compiled_grammar.scan_with("names", "bar foo bar", &mut second).unwrap(), vec![3]);
+    assert_eq!(compiled_grammar.scan_with("name", "bar", &mut second).unwrap(), vec![0]);
+    assert_eq!(second.ids.len(), 2);
+
+    assert_eq!(compiled_grammar.scan("name", "baz").unwrap(), vec![0]);
+    assert_eq!(first.ids.len(), 2);
+}
+
+#[test]
+fn scan_with_error() {
+    let mut grammar: Grammar<i32, Vec<i32>> = Grammar::new();
+    grammar.map_with_context("num", "[0-9]+", |_, l, ctx: &mut Vec<i32>| {
+        let x = l.parse::<i32>().unwrap();
+
+        if ctx.contains(&x) {
+            return Err(format!("Number {} already used.", x));
+        }
+
+        ctx.push(x);
+        Ok(x)
+    });
+    grammar.rule("nums", "<num>(,<num>)*");
+
+    let compiled_grammar = grammar.compile().unwrap();
+    let mut used = vec![];
+
+    match compiled_grammar.scan_with("nums", "1,2,1", &mut used) {
+        Err(GrammarError::Branch { rule, msg }) => {
+            assert_eq!(rule, "num");
+            assert_eq!(msg, "Number 1 already used.");
+        },
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    assert_eq!(used, vec![1, 2]);
+}
+
+#[test]
+fn scan_with_memoize() {
+    let mut grammar: Grammar<usize, Vec<String>> = Grammar::new();
+    grammar.map_with_context("word", "[a-z]+", |_, l, ctx: &mut Vec<String>| {
+        ctx.push(l.to_string());
+        Ok(ctx.len())
+    });
+    grammar.map("item", "<word>", |b, _| Ok(b[0]));
+    grammar.map("line", "(<item>;|<item>,)", |b, _| Ok(b[0]));
+
+    let mut compiled_grammar = grammar.compile().unwrap();
+
+    let mut plain = vec![];
+    assert_eq!(compiled_grammar.scan_with("line", "abc,", &mut plain).unwrap(), vec![2]);
+
+    compiled_grammar.memoize(true);
+
+    let mut memoized = vec![];
+    assert_eq!(compiled_grammar.scan_with("line", "abc,", &mut memoized).unwrap(), vec![2]);
+    assert_eq!(memoized, plain);
+}
@@ -0,0 +1,26 @@
+use grammar::{CompiledGrammar, Grammar};
+use std::sync::Arc;
+use std::thread;
+
+fn is_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn send_sync() {
+    is_send_sync::<CompiledGrammar<f64>>();
+    is_send_sync::<CompiledGrammar<String, Vec<String>>>();
+
+    let offset = 100;
+    let mut grammar: Grammar<i32> = Grammar::new();
+    grammar.map("num", "[0-9]+", move |_, l| Ok(l.parse::<i32>().unwrap() + offset));
+    grammar.map("sum", "<num>(,<num>)*", |b, _| Ok(b.iter().sum()));
+
+    let compiled_grammar = Arc::new(grammar.compile().unwrap());
+
+    let handles: Vec<_> = (0..4).map(|i| {
+        let compiled_grammar = Arc::clone(&compiled_grammar);
+        thread::spawn(move || compiled_grammar.scan("sum", &format!("{},{}", i, i)).unwrap()[0])
+    }).collect();
+
+    let sums: Vec<i32> = handles.into_iter().map(|x| x.join().unwrap()).collect();
+    assert_eq!(sums, vec![200, 202, 204, 206]);
+}
@@ -0,0 +1,29 @@
+use grammar::{Expected, Grammar, GrammarError};
+
+#[test]
+fn try_rule() {
+    let mut grammar: Grammar<i32> = Grammar::new();
+    assert!(grammar.try_rule("monkey", "monkey").is_ok());
+    assert!(grammar.try_map("gorilla", "gorilla", |_, _| Ok(1)).is_ok());
+
+    match grammar.try_rule("monkey", "chimp").err() {
+        Some(GrammarError::DuplicateRule { id }) => assert_eq!(id, "monkey"),
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    let err = grammar.try_rule("ape", "ape<monkey").unwrap_err();
+    assert_eq!(err.rule(), Some("ape"));
+    assert_eq!(err.position().unwrap().offset, 10);
+    assert!(err.expected().contains(&Expected::Literal(String::from(">"))));
+
+    let err = grammar.try_map("bonobo", "bonobo{2,x}", |_, _| Ok(2)).unwrap_err();
+    assert_eq!(err.rule(), Some("bonobo"));
+    assert_eq!(err.position().unwrap().offset, 9);
+    assert_eq!(err.expected(), &[Expected::CharRange { start: '0', end: '9' }, Expected::Rule(String::from("integer"))]);
+
+    // The failed rules are not added.
+    let compiled = grammar.compile().unwrap();
+    assert!(compiled.scan("monkey", "monkey").is_ok());
+    assert!(compiled.scan("ape", "ape").is_err());
+    assert!(compiled.scan("bonobo", "").is_err());
+}
@@ -0,0 +1,21 @@
+use grammar::{Grammar, GrammarError, UnknownRule};
+
+#[test]
+fn unknown_rules() {
+    let mut grammar: Grammar<i32> = Grammar::new_with_ws("<blank>");
+    grammar.rule("monkey", "monkey");
+    grammar.rule("zoo", "<monkey> <gorilla>(<chimp>|<monkey>)");
+    grammar.rule("jungle", "<zoo>*<tiger>");
+
+    let unknown = match grammar.compile().err() {
+        Some(GrammarError::UnknownRules(unknown)) => unknown,
+        other => panic!("Unexpected {:?}", other),
+    };
+
+    assert_eq!(unknown, vec![
+        UnknownRule { id: String::from("blank"), rule: None, clause: 0 },
+        UnknownRule { id: String::from("tiger"), rule: Some(String::from("jungle")), clause: 1 },
+        UnknownRule { id: String::from("gorilla"), rule: Some(String::from("zoo")), clause: 2 },
+        UnknownRule { id: String::from("chimp"), rule: Some(String::from("zoo")), clause: 3 },
+    ]);
+}
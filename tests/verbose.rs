@@ -0,0 +1,62 @@
+use grammar::Grammar;
+
+#[test]
+fn verbose() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("name", "[a-z]+", |_, l| Ok(l.to_string()));
+    grammar.map("assign", r"(?x)
+        let _ <name>        # the variable
+        _* = _*             # optional whitespace around the equals sign
+        <name> (_* , _* <name>)*
+        _* ;
+    ", |b, _| Ok(b.join(",")));
+    grammar.map("escaped", r"(?x) a\ b \# [\ \#]+ @Expected a closing c@ c", |_, l| Ok(l.to_string()));
+    grammar.map("plain", "a _*#", |_, l| Ok(l.to_string()));
+
+    let compiled_grammar = grammar.compile().unwrap();
+
+    assert_eq!(compiled_grammar.scan("assign", "let x = y, z;").unwrap(), vec!["x,y,z"]);
+    assert_eq!(compiled_grammar.scan("assign", "let x=y;").unwrap(), vec!["x,y"]);
+    assert!(compiled_grammar.scan("assign", "letx=y;").is_err());
+    assert_eq!(compiled_grammar.scan("escaped", "a b# #c").unwrap(), vec!["a b# #c"]);
+    assert!(compiled_grammar.scan("escaped", "a b# x").unwrap_err().to_string().contains("Expected a closing c"));
+    assert_eq!(compiled_grammar.scan("plain", "a  \t#").unwrap(), vec!["a  \t#"]);
+}
+
+#[test]
+fn verbose_syntax_error() {
+    let err = Grammar::<String>::new().try_rule("x", "(?x)\n  abc  # comment\n  (d|e").unwrap_err();
+    let position = err.position().unwrap();
+    assert_eq!((position.line, position.column), (3, 7));
+}
+
+#[test]
+fn verbose_at_sign() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("set", "(?x) [@] # at sign\n b", |_, l| Ok(l.to_string()));
+    grammar.map("alter", "(?x) (~@,at|\\#,hash) # alter\n ( b )", |_, l| Ok(l.to_string()));
+    grammar.map("not", "(?x) [^@ ;]+ @No more@ ;", |_, l| Ok(l.to_string()));
+
+    let compiled_grammar = grammar.compile().unwrap();
+
+    assert_eq!(compiled_grammar.scan("set", "@b").unwrap(), vec!["@b"]);
+    assert_eq!(compiled_grammar.scan("alter", "@b").unwrap(), vec!["atb"]);
+    assert_eq!(compiled_grammar.scan("alter", "#b").unwrap(), vec!["hashb"]);
+    assert_eq!(compiled_grammar.scan("not", "ab;").unwrap(), vec!["ab;"]);
+    assert!(compiled_grammar.scan("not", "a b;").is_err());
+    assert!(compiled_grammar.scan("not", "ab@").unwrap_err().to_string().contains("No more"));
+}
+
+#[test]
+fn verbose_set_layout() {
+    let mut grammar: Grammar<String> = Grammar::new();
+    grammar.map("hash", "(?x) [a#]+ # a or hash", |_, l| Ok(l.to_string()));
+    grammar.map("space", "(?x) [a b]+", |_, l| Ok(l.to_string()));
+    grammar.map("alter", "(?x) (~a, b) c", |_, l| Ok(l.to_string()));
+
+    let compiled_grammar = grammar.compile().unwrap();
+
+    assert_eq!(compiled_grammar.scan("hash", "a#a").unwrap(), vec!["a#a"]);
+    assert_eq!(compiled_grammar.scan("space", "a b").unwrap(), vec!["a b"]);
+    assert_eq!(compiled_grammar.scan("alter", "ac").unwrap(), vec![" bc"]);
+}